@@ -1,4 +1,13 @@
+use crate::collector::{self, MAX_ARG_STRLEN};
+use crate::duration;
+use crate::proc::{Halt, Mode, Timeout};
+use crate::size;
+use crate::source::{Cut, Delimiter, Format};
+use regex::bytes::Regex;
+use std::fmt;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 use structopt::StructOpt;
 
 /// run mutliple commands based off of input
@@ -8,6 +17,46 @@ pub struct Cli {
     #[structopt(short = "0", long = "null")]
     pub null_separated: bool,
 
+    /// separate input by a delimiter (escapes such as \t, \r\n and \x1e are allowed)
+    #[structopt(short = "d", long = "delimiter")]
+    pub delimiter: Option<Delimiter>,
+
+    /// split input into words like xargs (blanks separate, quotes and backslashes escape)
+    #[structopt(long = "xargs-parse")]
+    pub xargs_parse: bool,
+
+    /// split each record into columns filling %1, %2, ...
+    #[structopt(short = "C", long = "colsep")]
+    pub colsep: Option<Delimiter>,
+
+    /// parse input as csv, each row filling %1, %2, ... (separated by --colsep or ',')
+    #[structopt(long = "csv")]
+    pub csv: bool,
+
+    /// parse each record as a json object, filling placeholders like {.user.id} or {.files[0]}
+    #[structopt(long = "jsonl")]
+    pub jsonl: bool,
+
+    /// what to do with records that are not valid utf-8 or json (skip or fail)
+    #[structopt(long = "on-invalid", default_value = "fail")]
+    pub on_invalid: OnInvalid,
+
+    /// write chunks of input records to the stdin of commands instead of passing arguments
+    #[structopt(long = "pipe")]
+    pub pipe: bool,
+
+    /// size of the chunks written in pipe mode, rounded up to whole records (defaults to 1M)
+    #[structopt(long = "block", parse(try_from_str = size::parse))]
+    pub block: Option<usize>,
+
+    /// pattern matching the start of multi-line records in pipe mode
+    #[structopt(long = "recstart")]
+    pub recstart: Option<Regex>,
+
+    /// stream chunks to --parallel commands started once instead of one command per chunk
+    #[structopt(long = "round-robin")]
+    pub round_robin: bool,
+
     /// read file instead of stdin
     #[structopt(short = "a", long = "args")]
     pub args: Option<PathBuf>,
@@ -16,14 +65,90 @@ pub struct Cli {
     #[structopt(short = "j", long = "join")]
     pub join: bool,
 
+    /// run one batch per key, a template like {//} or a column number
+    #[structopt(long = "group-by")]
+    pub group_by: Option<String>,
+
+    /// input is sorted by the group key, batches run as soon as the key changes
+    #[structopt(long = "sorted")]
+    pub sorted: bool,
+
     /// join a certain amount of input lines
     #[structopt(short = "l", long = "lines")]
     pub lines: Option<u32>,
 
+    /// split batches so command lines stay below n bytes (defaults to the system limit)
+    #[structopt(short = "s", long = "max-chars", parse(try_from_str = size::parse))]
+    pub max_chars: Option<usize>,
+
+    /// run a partial batch once its first input waited this long (e.g. 5s)
+    #[structopt(long = "batch-timeout", parse(try_from_str = duration::parse))]
+    pub batch_timeout: Option<Duration>,
+
     /// run n parallel jobs (defaults to the number of cores)
     #[structopt(short = "p", long = "parallel")]
     pub parallel: Option<u16>,
 
+    /// stop starting commands once a condition is met, e.g. now,fail=1 or soon,success=50% (percentages apply once 3 jobs completed)
+    #[structopt(long = "halt")]
+    pub halt: Option<Halt>,
+
+    /// terminate commands running longer than a duration (e.g. 30s) or a percentage of the median runtime (e.g. 200%)
+    #[structopt(long = "timeout")]
+    pub timeout: Option<Timeout>,
+
+    /// time a timed out command gets to terminate before it is killed
+    #[structopt(long = "kill-after", default_value = "1s", parse(try_from_str = duration::parse))]
+    pub kill_after: Duration,
+
+    /// run failing commands again up to n times
+    #[structopt(long = "retries", default_value = "0")]
+    pub retries: u32,
+
+    /// time to wait before running a failed command again
+    #[structopt(long = "retry-delay", default_value = "0s", parse(try_from_str = duration::parse))]
+    pub retry_delay: Duration,
+
+    /// double the retry delay with every retry
+    #[structopt(long = "retry-backoff")]
+    pub retry_backoff: bool,
+
+    /// write the output of a command at once when it finishes
+    #[structopt(long = "group")]
+    pub group: bool,
+
+    /// write the output of commands by whole lines
+    #[structopt(long = "line-buffer")]
+    pub line_buffer: bool,
+
+    /// write the output of commands in input order (implies --group)
+    #[structopt(short = "k", long = "keep-order")]
+    pub keep_order: bool,
+
+    /// prefix output lines with the inputs of the command
+    #[structopt(long = "tag")]
+    pub tag: bool,
+
+    /// prefix output lines with a template, {#} is the job number and {time} its start (implies --tag)
+    #[structopt(long = "tagstring")]
+    pub tagstring: Option<String>,
+
+    /// let commands write directly (the default)
+    #[structopt(short = "u", long = "ungroup")]
+    pub ungroup: bool,
+
+    /// write a tab separated record of every job to a file
+    #[structopt(long = "joblog")]
+    pub joblog: Option<PathBuf>,
+
+    /// skip jobs the job log already records
+    #[structopt(long = "resume")]
+    pub resume: bool,
+
+    /// skip jobs the job log records as successful, running failed ones again
+    #[structopt(long = "resume-failed")]
+    pub resume_failed: bool,
+
     /// open /dev/tty before running a command (implies --parallel 1)
     #[structopt(short = "T", long = "tty")]
     pub tty: bool,
@@ -32,10 +157,18 @@ pub struct Cli {
     #[structopt(short = "A", long = "ask")]
     pub ask: bool,
 
+    /// print the commands that would run to stdout without running them
+    #[structopt(long = "dry-run")]
+    pub dry_run: bool,
+
     /// trace executions
     #[structopt(short = "t", long = "trace")]
     pub trace: bool,
 
+    /// run the command as a shell script with quoted inputs (--shell=bash to pick the shell, defaults to $SHELL)
+    #[structopt(short = "S", long = "shell", require_equals = true)]
+    pub shell: Option<Option<String>>,
+
     /// input substitution pattern
     #[structopt(short = "P", long = "pattern", default_value = "%")]
     pub pattern: String,
@@ -44,6 +177,13 @@ pub struct Cli {
     pub command: Vec<String>,
 }
 
+/// Policy for input records that can not be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnInvalid {
+    Skip,
+    Fail,
+}
+
 impl Cli {
     /// validates cli arguments
     ///
@@ -63,13 +203,197 @@ impl Cli {
         self
     }
 
+    /// the delimiter input records are separated by
+    pub fn record_delimiter(&self) -> Delimiter {
+        match &self.delimiter {
+            Some(delimiter) => delimiter.clone(),
+            None if self.null_separated => Delimiter::null(),
+            None => Delimiter::newline(),
+        }
+    }
+
+    /// the shell to run commands with in shell mode
+    pub fn shell(&self) -> Option<String> {
+        match &self.shell {
+            Some(Some(shell)) => Some(shell.clone()),
+            Some(None) => Some(std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())),
+            None => None,
+        }
+    }
+
+    /// how the output of commands is written
+    pub fn output_mode(&self) -> Mode {
+        if self.group || self.keep_order {
+            Mode::Group
+        } else if self.line_buffer || self.tagged() {
+            Mode::LineBuffer
+        } else if self.ask && self.is_parallel() && !self.ungroup {
+            // output written directly would clobber the prompt
+            Mode::Group
+        } else {
+            Mode::Ungroup
+        }
+    }
+
+    /// whether jobs of an earlier run are skipped
+    pub fn resuming(&self) -> bool {
+        self.resume || self.resume_failed
+    }
+
+    /// whether output lines are prefixed
+    pub fn tagged(&self) -> bool {
+        self.tag || self.tagstring.is_some()
+    }
+
+    /// when a chunk of records is complete in pipe mode
+    pub fn cut(&self) -> Cut {
+        match (self.lines, self.block) {
+            (Some(lines), _) => Cut::Records(lines),
+            (None, Some(block)) => Cut::Bytes(block),
+            (None, None) => Cut::Bytes(1 << 20),
+        }
+    }
+
+    /// the bytes of a command line the system accepts
+    pub fn arg_max(&self) -> usize {
+        let max = collector::arg_max();
+
+        // the script of a shell is a single argument
+        if self.shell.is_some() {
+            max.min(MAX_ARG_STRLEN)
+        } else {
+            max
+        }
+    }
+
+    /// how input is split into items
+    pub fn format(&self) -> Format {
+        if self.xargs_parse {
+            Format::Words
+        } else if let (true, Some(separator)) = (self.csv, self.csv_separator()) {
+            Format::Csv(separator)
+        } else {
+            Format::Records(self.record_delimiter(), self.colsep.clone())
+        }
+    }
+
+    /// whether records are split into columns
+    pub fn columns(&self) -> bool {
+        self.colsep.is_some() || self.csv
+    }
+
+    /// the byte separating csv fields
+    pub fn csv_separator(&self) -> Option<u8> {
+        match &self.colsep {
+            Some(colsep) => match colsep.as_bytes() {
+                [b] => Some(*b),
+                _ => None,
+            },
+            None => Some(b','),
+        }
+    }
+
     fn validation_message(&self) -> Option<&'static str> {
+        if self.null_separated && self.delimiter.is_some() {
+            return Some("null separation and a delimiter may not both be specified");
+        }
+
+        if self.xargs_parse && (self.null_separated || self.delimiter.is_some()) {
+            return Some("xargs parsing may not be combined with a delimiter");
+        }
+
+        if self.xargs_parse && (self.colsep.is_some() || self.csv) {
+            return Some("xargs parsing may not be combined with columns");
+        }
+
+        if self.csv && (self.null_separated || self.delimiter.is_some()) {
+            return Some("csv rows may not be combined with a delimiter");
+        }
+
+        if self.jsonl && (self.xargs_parse || self.columns()) {
+            return Some("json lines may not be combined with other input formats");
+        }
+
+        // inputs no placeholder refers to would be appended as raw json
+        if self.jsonl && (self.lines.is_some() || self.join || self.group_by.is_some()) {
+            return Some("json lines may not be combined with lines, join or group-by");
+        }
+
+        if self.csv && self.csv_separator().is_none() {
+            return Some("csv column separator must be a single byte");
+        }
+
+        if self.pipe && (self.xargs_parse || self.columns() || self.jsonl) {
+            return Some("pipe mode may not be combined with other input formats");
+        }
+
+        if self.pipe && (self.join || self.tty) {
+            return Some("pipe mode may not be combined with join or tty");
+        }
+
+        if (self.block.is_some() || self.recstart.is_some()) && !self.pipe {
+            return Some("block and record start only apply to pipe mode");
+        }
+
+        if (self.max_chars.is_some() || self.batch_timeout.is_some()) && self.pipe {
+            return Some("max chars and batch timeout do not apply to pipe mode");
+        }
+
+        if self.group_by.is_some() && self.pipe {
+            return Some("grouping does not apply to pipe mode");
+        }
+
+        if self.sorted && self.group_by.is_none() {
+            return Some("sorted input requires a group key");
+        }
+
+        if self.round_robin && !self.pipe {
+            return Some("round robin requires pipe mode");
+        }
+
+        if self.round_robin && (self.resuming() || self.retries > 0) {
+            return Some("streamed input may not be resumed or retried");
+        }
+
+        if self.block.is_some() && self.lines.is_some() {
+            return Some("block and lines may not both be specified");
+        }
+
         if self.join && self.lines.is_some() {
             return Some(
                 "arbitrary join and join on a certain number of lines may not both be specified",
             );
         }
 
+        if [self.group, self.line_buffer, self.ungroup]
+            .iter()
+            .filter(|m| **m)
+            .count()
+            > 1
+        {
+            return Some("only one of group, line buffer and ungroup may be specified");
+        }
+
+        if self.keep_order && (self.line_buffer || self.ungroup) {
+            return Some("keeping order requires grouped output");
+        }
+
+        if self.tagged() && self.ungroup {
+            return Some("tagging requires grouped or line buffered output");
+        }
+
+        if self.resuming() && self.joblog.is_none() {
+            return Some("resuming requires a job log");
+        }
+
+        if self.resume && self.resume_failed {
+            return Some("only one of resume and resume failed may be specified");
+        }
+
+        if self.dry_run && (self.ask || self.tty) {
+            return Some("a dry run may not be interactive");
+        }
+
         if self.lines == Some(0) {
             return Some("Lines may not be zero");
         }
@@ -79,10 +403,6 @@ impl Cli {
                 return Some("parallel jobs may not be interactive");
             }
 
-            if self.ask {
-                return Some("parallel jobs may not ask before execution");
-            }
-
             if self.join {
                 return Some("one job may not be parallel");
             }
@@ -92,7 +412,7 @@ impl Cli {
             return Some("a command must be specified to execute");
         }
 
-        if &self.command[0] == &self.pattern {
+        if self.command[0] == self.pattern {
             return Some("a command may not start with the pattern");
         }
 
@@ -103,3 +423,24 @@ impl Cli {
         !matches!(self.parallel, Some(1) | None)
     }
 }
+
+impl FromStr for OnInvalid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OnInvalid, String> {
+        match s {
+            "skip" => Ok(OnInvalid::Skip),
+            "fail" => Ok(OnInvalid::Fail),
+            _ => Err(format!("expected 'skip' or 'fail', found '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for OnInvalid {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OnInvalid::Skip => write!(fmt, "skip"),
+            OnInvalid::Fail => write!(fmt, "fail"),
+        }
+    }
+}
@@ -1,10 +1,47 @@
+use crate::invoker::Size;
+use crate::source::Decoded;
+use serde_json::Value;
+
+use std::collections::HashMap;
+use std::mem::size_of;
 use std::num::NonZeroU32;
+use std::time::{Duration, Instant};
+
+/// room left for what the system adds to the arguments, like xargs does
+const HEADROOM: usize = 2048;
+
+/// the longest single argument linux accepts
+pub const MAX_ARG_STRLEN: usize = 32 * 4096;
 
 pub struct Collector {
     limit: Limit,
     store: Vec<String>,
+    /// the parsed inputs of json records
+    docs: Vec<Value>,
+    records: u32,
+    /// the command line without inputs
+    base: Size,
+    /// bytes a command line may take as counted by `--max-chars`
+    max_chars: Option<usize>,
+    /// bytes the system accepts, counting argument pointers as well
+    arg_max: Option<usize>,
+    size: Size,
+    timeout: Option<Duration>,
+    since: Option<Instant>,
 }
 
+/// Batches of inputs sharing a key
+///
+/// with sorted input a batch ends when the key changes, otherwise every
+/// key keeps its batch until the input ends.
+pub struct Groups {
+    batches: Vec<(String, Collector)>,
+    index: HashMap<String, usize>,
+    current: usize,
+    sorted: bool,
+}
+
+#[derive(Clone, Copy)]
 pub enum Limit {
     Limit(NonZeroU32),
     Unlimited,
@@ -14,26 +51,100 @@ impl Collector {
     pub fn new(limit: Limit) -> Collector {
         let store = Vec::with_capacity(limit.hint());
 
-        Collector { limit, store }
+        Collector {
+            limit,
+            store,
+            docs: Vec::new(),
+            records: 0,
+            base: Size::default(),
+            max_chars: None,
+            arg_max: None,
+            size: Size::default(),
+            timeout: None,
+            since: None,
+        }
     }
 
-    pub fn full(&self) -> bool {
-        match self.limit {
-            Limit::Limit(limit) if self.store.len() as u32 >= limit.into() => true,
-            _ => false,
+    /// An empty collector limited like this one
+    fn fresh(&self) -> Collector {
+        Collector {
+            base: self.base,
+            max_chars: self.max_chars,
+            arg_max: self.arg_max,
+            timeout: self.timeout,
+            ..Collector::new(self.limit)
         }
     }
 
-    pub fn push(&mut self, text: String) {
-        self.store.push(text);
+    /// Gives a batch `timeout` from its first record to fill up
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Collector {
+        self.timeout = timeout;
+        self
+    }
+
+    /// When a partial batch is due to run
+    pub fn deadline(&self) -> Option<Instant> {
+        Some(self.since? + self.timeout?)
+    }
+
+    /// Limits the command lines of batches, `base` is the size without inputs
+    ///
+    /// `max_chars` counts like xargs, `arg_max` like the system which also
+    /// counts the pointer of every argument.
+    pub fn with_limits(
+        mut self,
+        base: Size,
+        max_chars: Option<usize>,
+        arg_max: usize,
+    ) -> Collector {
+        self.base = base;
+        self.max_chars = max_chars;
+        self.arg_max = Some(arg_max);
+        self
+    }
+
+    pub fn full(&self) -> bool {
+        matches!(self.limit, Limit::Limit(limit) if self.records >= limit.into())
+    }
+
+    /// Whether a record adding `size` to the command line still fits the batch
+    pub fn fits(&self, size: Size) -> bool {
+        let total = self.base + self.size + size;
+        let pointers = (total.args + 1) * size_of::<*const u8>();
+
+        self.max_chars.is_none_or(|max| total.chars <= max)
+            && self.arg_max.is_none_or(|max| total.chars + pointers <= max)
+    }
+
+    /// Pushes a record, each of its columns becomes an input
+    ///
+    /// `size` is what the record adds to the command line.
+    pub fn push(&mut self, record: Decoded, size: Size) {
+        if self.records == 0 && self.timeout.is_some() {
+            self.since = Some(Instant::now());
+        }
+
+        self.store.extend(record.columns);
+        self.docs.extend(record.docs);
+        self.records += 1;
+        self.size = self.size + size;
     }
 
     pub fn refs(&self) -> Vec<&str> {
         self.store.iter().map(|s| s.as_str()).collect()
     }
 
+    /// the parsed inputs, empty unless records are json
+    pub fn docs(&self) -> &[Value] {
+        &self.docs
+    }
+
     pub fn clear(&mut self) {
         self.store.clear();
+        self.docs.clear();
+        self.records = 0;
+        self.size = Size::default();
+        self.since = None;
     }
 
     pub fn is_empty(&self) -> bool {
@@ -41,6 +152,86 @@ impl Collector {
     }
 }
 
+impl Groups {
+    /// Starts with the batch of the empty key, `collector` limits all batches
+    pub fn new(collector: Collector, sorted: bool) -> Groups {
+        let mut index = HashMap::new();
+        index.insert(String::new(), 0);
+
+        Groups {
+            batches: vec![(String::new(), collector)],
+            index,
+            current: 0,
+            sorted,
+        }
+    }
+
+    /// Makes the batch of `key` the current one
+    ///
+    /// returns the batch a new key ended with sorted input.
+    pub fn select(&mut self, key: String) -> Option<Collector> {
+        if self.sorted {
+            let (current, batch) = &mut self.batches[0];
+
+            if *current == key {
+                return None;
+            }
+
+            let fresh = batch.fresh();
+            *current = key;
+
+            return Some(std::mem::replace(batch, fresh)).filter(|b| !b.is_empty());
+        }
+
+        self.current = match self.index.get(&key) {
+            Some(current) => *current,
+            None => {
+                let fresh = self.batches[0].1.fresh();
+
+                self.index.insert(key.clone(), self.batches.len());
+                self.batches.push((key, fresh));
+                self.batches.len() - 1
+            }
+        };
+
+        None
+    }
+
+    pub fn current(&mut self) -> &mut Collector {
+        &mut self.batches[self.current].1
+    }
+
+    /// When the first partial batch is due to run
+    pub fn deadline(&self) -> Option<Instant> {
+        self.batches[0].1.timeout?;
+
+        self.batches.iter().filter_map(|(_, b)| b.deadline()).min()
+    }
+
+    /// All batches in the order their keys first appeared
+    pub fn batches(&mut self) -> impl Iterator<Item = &mut Collector> {
+        self.batches.iter_mut().map(|(_, batch)| batch)
+    }
+}
+
+/// The longest command line the system accepts, less the environment commands inherit
+///
+/// the system counts the pointers to the arguments and variables as well.
+pub fn arg_max() -> usize {
+    let pointer = size_of::<*const u8>();
+
+    let max = match unsafe { libc::sysconf(libc::_SC_ARG_MAX) } {
+        max if max > 0 => max as usize,
+        _ => 128 * 1024,
+    };
+
+    let env: usize = std::env::vars_os()
+        .map(|(key, value)| key.len() + value.len() + 2 + pointer)
+        .sum();
+
+    max.saturating_sub(env + pointer + HEADROOM)
+}
+
 impl Limit {
     fn hint(&self) -> usize {
         match self {
@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+/// Parses a duration like `500ms`, `10s`, `2m`, `1h` or plain seconds (`1.5`)
+pub fn parse(s: &str) -> Result<Duration, String> {
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+
+    let (amount, unit) = s.split_at(split);
+
+    let amount: f64 = amount
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", s))?;
+
+    let scale = match unit {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => {
+            return Err(format!(
+                "invalid duration unit '{}' (use ms, s, m or h)",
+                unit
+            ))
+        }
+    };
+
+    Duration::try_from_secs_f64(amount * scale).map_err(|_| format!("invalid duration '{}'", s))
+}
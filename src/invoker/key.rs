@@ -0,0 +1,23 @@
+use super::template::{Cursor, Template};
+
+/// What inputs are grouped by
+#[derive(Debug, PartialEq, Eq)]
+pub struct Key(Template);
+
+impl Key {
+    /// `key` is a template filled like the command or a column number
+    pub fn new(pattern: &str, key: &str) -> Key {
+        let template = if !key.is_empty() && key.bytes().all(|b| b.is_ascii_digit()) {
+            format!("{}{}", pattern, key)
+        } else {
+            key.to_string()
+        };
+
+        Key(Template::new(pattern, template))
+    }
+
+    /// the key of a record from its columns
+    pub fn of(&self, columns: &[&str]) -> String {
+        self.0.apply(&mut Cursor::new(columns, false))
+    }
+}
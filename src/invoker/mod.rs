@@ -1,24 +1,56 @@
+use serde_json::Value;
 use std::borrow::Cow;
+use std::ops::Add;
+use std::sync::Arc;
 
+mod key;
+mod modifier;
 mod preview;
+mod quote;
+mod tag;
 mod template;
 
 #[cfg(test)]
 mod test;
 
+pub use key::Key;
 pub use preview::{DetachedPreview, Preview};
-use template::Template;
+use quote::quote;
+pub use tag::Tag;
+use tag::TagFormat;
+use template::{Cursor, Syntax, Template};
+
+/// Length of a command line, counted like xargs does
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    /// bytes of the arguments with their terminating nulls
+    pub chars: usize,
+    /// number of arguments
+    pub args: usize,
+}
 
 /// Takes patterns and fills them input and executes command
 #[derive(Debug, PartialEq, Eq)]
 pub struct Invoker {
     offsets: Vec<usize>,
     templates: Vec<Template>,
+    shell: Option<String>,
+    tag: Option<Arc<TagFormat>>,
+    /// templates may refer to fields of json inputs
+    json: bool,
 }
 
 impl Invoker {
     /// Build an invoker based off of a template
+    #[cfg(test)]
     pub fn new(pattern: &str, command: Vec<String>) -> Invoker {
+        Invoker::parse(pattern, command, false)
+    }
+
+    /// Build an invoker, with `json` set templates may refer to fields of
+    /// json inputs like `{.user.id}`
+    pub fn parse(pattern: &str, command: Vec<String>, json: bool) -> Invoker {
+        let syntax = Syntax { job: false, json };
         let mut offsets = Vec::new();
         let mut templates = Vec::with_capacity(command.len());
 
@@ -26,15 +58,61 @@ impl Invoker {
             if part.as_str() == pattern {
                 offsets.push(i);
             } else {
-                templates.push(Template::new(pattern, part));
+                templates.push(Template::parse(pattern, part, syntax));
             }
         }
 
-        Invoker { offsets, templates }
+        Invoker {
+            offsets,
+            templates,
+            shell: None,
+            tag: None,
+            json,
+        }
+    }
+
+    /// Runs the filled command as a script of `shell`
+    ///
+    /// inputs are shell quoted when they are filled in.
+    pub fn with_shell(mut self, shell: Option<String>) -> Invoker {
+        self.shell = shell;
+        self
+    }
+
+    /// Prefixes the output lines of commands with their inputs
+    ///
+    /// `template` may refer to the inputs like the command does and to
+    /// the job with `{#}` and `{time}`.
+    pub fn with_tag(mut self, tag: bool, pattern: &str, template: Option<String>) -> Invoker {
+        self.tag = match template {
+            Some(template) => {
+                let syntax = Syntax {
+                    job: true,
+                    json: self.json,
+                };
+
+                Some(Arc::new(TagFormat::Template(Template::parse(
+                    pattern, template, syntax,
+                ))))
+            }
+            None if tag => Some(Arc::new(TagFormat::Inputs)),
+            None => None,
+        };
+        self
     }
 
     /// Creates command invocation preview
     pub fn preview<'s, 'a>(&'s self, inputs: &[&'a str]) -> Preview<'a>
+    where
+        's: 'a,
+    {
+        self.preview_json(inputs, &[])
+    }
+
+    /// Creates command invocation preview of json inputs
+    ///
+    /// `docs` are the parsed inputs fields are taken from.
+    pub fn preview_json<'s, 'a>(&'s self, inputs: &[&'a str], docs: &[Value]) -> Preview<'a>
     where
         's: 'a,
     {
@@ -45,44 +123,113 @@ impl Invoker {
 
         let mut offset_iter = self.offsets.iter().peekable();
         let mut static_iter = self.templates.iter();
-        let mut input_iter = inputs.iter();
+        let mut cursor = Cursor::new(inputs, self.shell.is_some()).with_docs(docs);
 
         loop {
             if Some(&&current) == offset_iter.peek() {
-                if let Some(input) = input_iter.next() {
+                if let Some(input) = cursor.next_input() {
                     offset_iter.next();
-                    fill.push(Cow::from(*input));
+                    fill.push(cursor.fill(input));
                 }
+            } else if let Some(s) = static_iter.next() {
+                fill.push(Cow::from(s.apply(&mut cursor)));
             } else {
-                if let Some(s) = static_iter.next() {
-                    fill.push(Cow::from(s.apply(&mut input_iter)));
-                } else if let Some(input) = input_iter.next() {
-                    fill.push(Cow::from(*input));
-                } else {
-                    break;
-                }
+                break;
             }
 
             current += 1;
         }
 
-        while let Some(input) = input_iter.next() {
-            fill.push(Cow::from(*input));
+        for input in cursor.rest() {
+            fill.push(cursor.fill(*input));
         }
 
-        Preview::new(fill)
+        let preview = match &self.shell {
+            Some(shell) => Preview::new(vec![
+                Cow::from(shell.as_str()),
+                Cow::from("-c"),
+                Cow::from(fill.join(" ")),
+            ]),
+            None => Preview::new(fill),
+        };
+
+        preview.with_tag(
+            self.tag
+                .as_ref()
+                .map(|format| Tag::new(format.clone(), inputs, docs)),
+        )
     }
 
+    /// The number of inputs one invocation takes
+    ///
+    /// This is the larger of the placeholders filled in order and
+    /// the highest position referred to.
     pub fn slots(&self) -> u32 {
-        let mut slots = self.offsets.len() as u32;
+        let mut sequential = self.offsets.len();
+        let mut positional = 0;
 
         for template in &self.templates {
-            match template {
-                Template::Interp { offsets, .. } => slots += offsets.len() as u32,
-                _ => (),
-            }
+            sequential += template.sequential();
+            positional = positional.max(template.positional());
+        }
+
+        sequential.max(positional) as u32
+    }
+
+    /// The highest position the command refers to
+    ///
+    /// batches with fewer inputs can not fill the command.
+    pub fn positions(&self) -> usize {
+        self.templates
+            .iter()
+            .map(Template::positional)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The size of the command line without inputs
+    pub fn base_size(&self) -> Size {
+        let templates = self.templates.iter().map(Template::base_len);
+
+        match &self.shell {
+            Some(shell) => Size {
+                chars: shell.len()
+                    + 1
+                    + "-c".len()
+                    + 1
+                    + templates.map(|len| len + 1).sum::<usize>(),
+                args: 3,
+            },
+            None => Size {
+                chars: templates.map(|len| len + 1).sum(),
+                args: self.templates.len(),
+            },
         }
+    }
 
-        slots
+    /// The size an input adds to the command line
+    pub fn input_size(&self, input: &str) -> Size {
+        match &self.shell {
+            // joined to the script by a space
+            Some(_) => Size {
+                chars: quote(input).len() + 1,
+                args: 0,
+            },
+            None => Size {
+                chars: input.len() + 1,
+                args: 1,
+            },
+        }
+    }
+}
+
+impl Add for Size {
+    type Output = Size;
+
+    fn add(self, other: Size) -> Size {
+        Size {
+            chars: self.chars + other.chars,
+            args: self.args + other.args,
+        }
     }
 }
@@ -0,0 +1,158 @@
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// Transformation applied to an input before it fills a placeholder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Modifier {
+    /// the input as is
+    None,
+    /// `{/}` everything after the last slash
+    Basename,
+    /// `{//}` everything before the last slash
+    Dirname,
+    /// `{.}` the input without its extension
+    StripExt,
+    /// `{/.}` the basename without its extension
+    BasenameStripExt,
+    /// `{ext}` the extension without its dot
+    Ext,
+    /// `{.user.id}` a value extracted from a json input
+    Field(Vec<Segment>),
+}
+
+/// Step of a json field path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl Modifier {
+    /// parses the modifier part of a braced placeholder
+    pub fn parse(s: &str) -> Option<Modifier> {
+        match s {
+            "" => Some(Modifier::None),
+            "/" => Some(Modifier::Basename),
+            "//" => Some(Modifier::Dirname),
+            "." => Some(Modifier::StripExt),
+            "/." => Some(Modifier::BasenameStripExt),
+            "ext" => Some(Modifier::Ext),
+            _ => parse_path(s).map(Modifier::Field),
+        }
+    }
+
+    /// transforms `input`, fields are taken from its parsed `json`
+    pub fn apply<'a>(&self, input: &'a str, json: Option<&Value>) -> Cow<'a, str> {
+        match self {
+            Modifier::None => Cow::from(input),
+            Modifier::Basename => Cow::from(basename(input)),
+            Modifier::Dirname => Cow::from(match input.rfind('/') {
+                Some(0) => "/",
+                Some(idx) => &input[..idx],
+                None => ".",
+            }),
+            Modifier::StripExt => Cow::from(strip_ext(input)),
+            Modifier::BasenameStripExt => Cow::from(strip_ext(basename(input))),
+            Modifier::Ext => Cow::from(match ext_dot(input) {
+                Some(idx) => &input[idx + 1..],
+                None => "",
+            }),
+            Modifier::Field(path) => Cow::from(match json {
+                Some(doc) => field(doc, path),
+                None => String::new(),
+            }),
+        }
+    }
+}
+
+/// extracts the value at `path` from a json document
+///
+/// strings are inserted without quotes, missing values and null are empty
+/// and other values are inserted as compact json.
+fn field(doc: &Value, path: &[Segment]) -> String {
+    let mut value = doc;
+
+    for segment in path {
+        let next = match segment {
+            Segment::Key(key) => value.get(key),
+            Segment::Index(idx) => value.get(idx),
+        };
+
+        match next {
+            Some(next) => value = next,
+            None => return String::new(),
+        }
+    }
+
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// parses a field path like `.user.id` or `.files[0]`
+fn parse_path(s: &str) -> Option<Vec<Segment>> {
+    let mut rest = s.strip_prefix('.')?;
+    let mut path = Vec::new();
+
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix('[') {
+            let end = tail.find(']')?;
+            path.push(Segment::Index(tail[..end].parse().ok()?));
+            rest = &tail[end + 1..];
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            if end == 0 {
+                return None;
+            }
+
+            path.push(Segment::Key(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+
+        if let Some(tail) = rest.strip_prefix('.') {
+            if tail.is_empty() {
+                return None;
+            }
+
+            rest = tail;
+        } else if !rest.is_empty() && !rest.starts_with('[') {
+            return None;
+        }
+    }
+
+    if path.is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+fn strip_ext(input: &str) -> &str {
+    match ext_dot(input) {
+        Some(idx) => &input[..idx],
+        None => input,
+    }
+}
+
+fn basename(input: &str) -> &str {
+    match input.rfind('/') {
+        Some(idx) => &input[idx + 1..],
+        None => input,
+    }
+}
+
+/// position of the dot starting the extension
+///
+/// dots leading a file name (`.bashrc`) do not start an extension.
+fn ext_dot(input: &str) -> Option<usize> {
+    let start = input.len() - basename(input).len();
+    let idx = input[start..].rfind('.')?;
+
+    if idx == 0 {
+        None
+    } else {
+        Some(start + idx)
+    }
+}
@@ -1,53 +1,55 @@
 use std::borrow::Cow;
-use std::fs::File;
-use std::io;
 use std::process::{Command, Stdio};
+use std::sync::Arc;
 
-#[cfg(unix)]
-use std::os::unix::io::{AsRawFd, FromRawFd};
-#[cfg(windows)]
-use std::os::windows::io::{AsRawHandle, FromRawHandle};
+use super::quote::escape;
+use super::tag::Tag;
 
 pub struct Preview<'a> {
     args: Vec<Cow<'a, str>>,
+    tag: Option<Tag>,
+    input: Option<Arc<[u8]>>,
 }
 
 #[derive(Debug)]
 pub struct DetachedPreview {
     args: Vec<String>,
+    tag: Option<Tag>,
+    input: Option<Arc<[u8]>>,
 }
 
 impl<'a> Preview<'a> {
     pub fn new(args: Vec<Cow<'a, str>>) -> Preview<'a> {
-        Preview { args }
+        Preview {
+            args,
+            tag: None,
+            input: None,
+        }
+    }
+
+    pub fn with_tag(mut self, tag: Option<Tag>) -> Preview<'a> {
+        self.tag = tag;
+        self
+    }
+
+    /// What the output lines of the command are prefixed with
+    pub fn tag(&self) -> Option<&Tag> {
+        self.tag.as_ref()
+    }
+
+    /// Writes `input` to the stdin of the command
+    pub fn with_input(mut self, input: impl Into<Arc<[u8]>>) -> Preview<'a> {
+        self.input = Some(input.into());
+        self
     }
 
-    pub fn run(&self) -> io::Result<u8> {
-        let status = Command::new(&*self.args[0])
-            .args(
-                &self.args[1..]
-                    .iter()
-                    .map(|s| s.as_ref())
-                    .collect::<Vec<&str>>(),
-            )
-            .stdin(Stdio::null())
-            .status()?;
-
-        Ok(status.code().unwrap_or(15) as u8)
+    pub fn input(&self) -> Option<Arc<[u8]>> {
+        self.input.clone()
     }
 
-    pub fn run_interactive(&self, tty: &File) -> io::Result<u8> {
-        let status = Command::new(&*self.args[0])
-            .args(
-                &self.args[1..]
-                    .iter()
-                    .map(|s| s.as_ref())
-                    .collect::<Vec<&str>>(),
-            )
-            .stdin(to_stdio(tty))
-            .status()?;
-
-        Ok(status.code().unwrap_or(15) as u8)
+    /// Builds the command to run, reading its input or from a null stdin
+    pub fn command(&self) -> Command {
+        build(&self.args, self.input.is_some())
     }
 
     pub fn detach(&self) -> DetachedPreview {
@@ -57,7 +59,11 @@ impl<'a> Preview<'a> {
             args.push(arg.to_string());
         }
 
-        DetachedPreview { args }
+        DetachedPreview {
+            args,
+            tag: self.tag.clone(),
+            input: self.input.clone(),
+        }
     }
 
     #[cfg(test)]
@@ -73,51 +79,49 @@ impl<'a> Preview<'a> {
 }
 
 impl DetachedPreview {
-    pub fn run(&self) -> io::Result<u8> {
-        let status = Command::new(&*self.args[0])
-            .args(
-                &self.args[1..]
-                    .iter()
-                    .map(|s| s.as_ref())
-                    .collect::<Vec<&str>>(),
-            )
-            .stdin(Stdio::null())
-            .status()?;
-
-        Ok(status.code().unwrap_or(15) as u8)
+    /// Builds the command to run, reading its input or from a null stdin
+    pub fn command(&self) -> Command {
+        build(&self.args, self.input.is_some())
+    }
+
+    pub fn tag(&self) -> Option<&Tag> {
+        self.tag.as_ref()
+    }
+
+    pub fn input(&self) -> Option<Arc<[u8]>> {
+        self.input.clone()
     }
 }
 
-impl std::fmt::Display for Preview<'_> {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(fmt, "{}", &self.args[0])?;
+fn build<S: AsRef<str>>(args: &[S], piped: bool) -> Command {
+    let mut command = Command::new(args[0].as_ref());
 
-        for s in &self.args[1..] {
-            write!(fmt, " {}", s)?;
-        }
+    command
+        .args(args[1..].iter().map(|s| s.as_ref()))
+        .stdin(if piped { Stdio::piped() } else { Stdio::null() });
+
+    command
+}
 
-        Ok(())
+impl std::fmt::Display for Preview<'_> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt_args(fmt, &self.args)
     }
 }
 
 impl std::fmt::Display for DetachedPreview {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(fmt, "{}", &self.args[0])?;
-
-        for s in &self.args[1..] {
-            write!(fmt, " {}", s)?;
-        }
-
-        Ok(())
+        fmt_args(fmt, &self.args)
     }
 }
 
-#[cfg(unix)]
-fn to_stdio(f: &File) -> Stdio {
-    unsafe { Stdio::from_raw_fd(f.as_raw_fd()) }
-}
+/// writes arguments shell quoted so the command can be pasted into a shell
+fn fmt_args<S: AsRef<str>>(fmt: &mut std::fmt::Formatter, args: &[S]) -> std::fmt::Result {
+    write!(fmt, "{}", escape(args[0].as_ref()))?;
+
+    for s in &args[1..] {
+        write!(fmt, " {}", escape(s.as_ref()))?;
+    }
 
-#[cfg(windows)]
-fn to_stdio(f: &File) -> Stdio {
-    unsafe { Stdio::from_raw_handle(f.as_raw_handle()) }
+    Ok(())
 }
@@ -0,0 +1,63 @@
+use std::borrow::Cow;
+
+/// Quotes a word for a POSIX shell
+///
+/// words made only of characters without special meaning are left as is,
+/// anything else is single quoted.
+pub fn quote(word: &str) -> Cow<'_, str> {
+    if !word.is_empty() && word.bytes().all(is_plain) {
+        return Cow::from(word);
+    }
+
+    let mut quoted = String::with_capacity(word.len() + 2);
+
+    quoted.push('\'');
+
+    for c in word.chars() {
+        if c == '\'' {
+            quoted.push_str("'\\''");
+        } else {
+            quoted.push(c);
+        }
+    }
+
+    quoted.push('\'');
+
+    Cow::from(quoted)
+}
+
+fn is_plain(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"_@%+=:,./-".contains(&b)
+}
+
+/// Quotes a word for display so it can be pasted back into a shell
+///
+/// words with control characters use `$'...'` quoting to keep them visible.
+pub fn escape(word: &str) -> Cow<'_, str> {
+    if !word.chars().any(char::is_control) {
+        return quote(word);
+    }
+
+    let mut escaped = String::with_capacity(word.len() + 3);
+
+    escaped.push_str("$'");
+
+    for c in word.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            '\'' => escaped.push_str("\\'"),
+            c if c.is_control() && (c as u32) < 0x100 => {
+                escaped.push_str(&format!("\\x{:02x}", c as u32));
+            }
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('\'');
+
+    Cow::from(escaped)
+}
@@ -0,0 +1,86 @@
+use super::template::{Cursor, Job, Template};
+
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What output lines of a job are prefixed with
+#[derive(Debug, PartialEq, Eq)]
+pub enum TagFormat {
+    /// the inputs of the job separated by spaces
+    Inputs,
+    Template(Template),
+}
+
+/// Prefix for the output lines of one job
+#[derive(Debug, Clone)]
+pub struct Tag {
+    format: Arc<TagFormat>,
+    inputs: Vec<String>,
+    /// the parsed inputs when they are json
+    docs: Vec<Value>,
+}
+
+impl Tag {
+    pub fn new(format: Arc<TagFormat>, inputs: &[&str], docs: &[Value]) -> Tag {
+        Tag {
+            format,
+            inputs: inputs.iter().map(|i| i.to_string()).collect(),
+            docs: docs.to_vec(),
+        }
+    }
+
+    /// renders the prefix for job `seq` starting now
+    pub fn render(&self, seq: usize) -> String {
+        let mut tag = match self.format.as_ref() {
+            TagFormat::Inputs => self.inputs.join(" "),
+            TagFormat::Template(template) => {
+                let inputs: Vec<&str> = self.inputs.iter().map(|i| i.as_str()).collect();
+                let job = Job {
+                    seq,
+                    time: timestamp(SystemTime::now()),
+                };
+
+                let mut cursor = Cursor::new(&inputs, false)
+                    .with_docs(&self.docs)
+                    .with_job(job);
+
+                template.apply(&mut cursor)
+            }
+        };
+
+        tag.push('\t');
+        tag
+    }
+}
+
+/// formats a time as UTC `YYYY-MM-DDTHH:MM:SSZ`
+pub fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let (days, rest) = ((secs / 86400) as i64, secs % 86400);
+
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
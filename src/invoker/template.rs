@@ -1,52 +1,123 @@
+use super::modifier::Modifier;
+use super::quote::quote;
+use serde_json::Value;
+use std::borrow::Cow;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Template {
     Static(String),
-    Interp { offsets: Vec<usize>, base: String },
+    Interp {
+        offsets: Vec<usize>,
+        slots: Vec<Slot>,
+        modifiers: Vec<Modifier>,
+        base: String,
+    },
+}
+
+/// Which input fills a placeholder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    /// the next unused input
+    Next,
+    /// the input of the bare placeholder, the one last taken in order or
+    /// else the next
+    Current,
+    /// the nth input of the batch (zero based)
+    Index(usize),
+    /// `{#}` the sequence number of the job
+    Seq,
+    /// `{time}` the time the job started
+    Time,
+}
+
+/// Placeholders a template knows besides those of the pattern and modifiers
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Syntax {
+    /// `{#}` and `{time}` refer to the job
+    pub job: bool,
+    /// `{.user.id}` refers to a field of a json input
+    pub json: bool,
+}
+
+/// Job a template is filled in for, known once the job starts
+pub struct Job {
+    pub seq: usize,
+    pub time: String,
+}
+
+/// Inputs of a batch being filled into templates
+pub struct Cursor<'a, 'b> {
+    inputs: &'b [&'a str],
+    /// the parsed json of every input, if inputs are json
+    docs: &'b [Value],
+    pos: usize,
+    used: usize,
+    quoting: bool,
+    job: Option<Job>,
 }
 
 impl Template {
     pub fn new(pattern: &str, s: String) -> Template {
-        if let Some(idx) = s.find(pattern) {
-            let mut offsets = vec![idx];
-            let mut rest = s.as_str();
-            let mut base = String::with_capacity(s.len());
-            let mut prev = idx;
+        Template::parse(pattern, s, Syntax::default())
+    }
 
-            base.insert_str(0, &rest[..idx]);
+    /// Builds a template that may also use the placeholders of `syntax`
+    pub fn parse(pattern: &str, s: String, syntax: Syntax) -> Template {
+        let mut offsets = Vec::new();
+        let mut slots = Vec::new();
+        let mut modifiers = Vec::new();
+        let mut base = String::with_capacity(s.len());
+        let mut rest = s.as_str();
 
-            rest = &rest[idx + pattern.len()..];
+        while let Some((idx, len, slot, modifier)) = find_placeholder(pattern, rest, syntax) {
+            base.push_str(&rest[..idx]);
+            offsets.push(base.len());
+            slots.push(slot);
+            modifiers.push(modifier);
 
-            while let Some(idx) = rest.find(pattern) {
-                offsets.push(idx + prev);
-                prev += idx;
-                base.push_str(&rest[..idx]);
+            rest = &rest[idx + len..];
+        }
 
-                rest = &rest[idx + pattern.len()..];
-            }
+        if offsets.is_empty() {
+            return Template::Static(s);
+        }
 
-            if !rest.is_empty() {
-                base.push_str(rest);
-            }
+        base.push_str(rest);
 
-            Template::Interp { offsets, base }
-        } else {
-            Template::Static(s)
+        Template::Interp {
+            offsets,
+            slots,
+            modifiers,
+            base,
         }
     }
 
-    pub fn apply(&self, iter: &mut dyn Iterator<Item = &&str>) -> String {
+    pub fn apply(&self, cursor: &mut Cursor) -> String {
         match self {
             Template::Static(s) => s.clone(),
-            Template::Interp { offsets, base } => {
+            Template::Interp {
+                offsets,
+                slots,
+                modifiers,
+                base,
+            } => {
                 let mut res = base.clone();
 
                 let mut adjust = 0;
 
-                for offset in offsets {
-                    if let Some(insert) = iter.next() {
-                        res.insert_str(adjust + offset, insert);
+                for ((offset, slot), modifier) in offsets.iter().zip(slots).zip(modifiers) {
+                    let insert = match slot {
+                        Slot::Seq => cursor.job.as_ref().map(|j| Cow::from(j.seq.to_string())),
+                        Slot::Time => cursor.job.as_ref().map(|j| Cow::from(j.time.as_str())),
+                        input => cursor
+                            .take(*input)
+                            .map(|i| cursor.fill(cursor.modify(i, modifier))),
+                    };
+
+                    if let Some(insert) = insert {
+                        res.insert_str(adjust + offset, &insert);
                         adjust += insert.len();
-                    } else {
+                    } else if *slot == Slot::Next {
                         break;
                     }
                 }
@@ -55,4 +126,196 @@ impl Template {
             }
         }
     }
+
+    /// the length of the template without its placeholders
+    pub fn base_len(&self) -> usize {
+        match self {
+            Template::Static(s) => s.len(),
+            Template::Interp { base, .. } => base.len(),
+        }
+    }
+
+    /// the number of inputs this template consumes in order
+    pub fn sequential(&self) -> usize {
+        match self {
+            Template::Static(_) => 0,
+            Template::Interp { slots, .. } => slots.iter().filter(|s| **s == Slot::Next).count(),
+        }
+    }
+
+    /// the number of inputs needed to fill the positional placeholders
+    pub fn positional(&self) -> usize {
+        match self {
+            Template::Static(_) => 0,
+            Template::Interp { slots, .. } => slots
+                .iter()
+                .filter_map(|s| match s {
+                    Slot::Current => Some(1),
+                    Slot::Index(i) => Some(i + 1),
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(0),
+        }
+    }
+}
+
+impl<'a, 'b> Cursor<'a, 'b> {
+    /// with `quoting` set inputs are shell quoted when filled in
+    pub fn new(inputs: &'b [&'a str], quoting: bool) -> Cursor<'a, 'b> {
+        Cursor {
+            inputs,
+            docs: &[],
+            pos: 0,
+            used: 0,
+            quoting,
+            job: None,
+        }
+    }
+
+    /// makes the parsed json of the inputs available to field placeholders
+    pub fn with_docs(mut self, docs: &'b [Value]) -> Cursor<'a, 'b> {
+        self.docs = docs;
+        self
+    }
+
+    /// makes the job available to `{#}` and `{time}` placeholders
+    pub fn with_job(mut self, job: Job) -> Cursor<'a, 'b> {
+        self.job = Some(job);
+        self
+    }
+
+    /// prepares an input to be filled into a placeholder
+    pub fn fill<'c>(&self, input: impl Into<Cow<'c, str>>) -> Cow<'c, str> {
+        let input = input.into();
+
+        if !self.quoting {
+            return input;
+        }
+
+        match input {
+            Cow::Borrowed(input) => quote(input),
+            Cow::Owned(input) => Cow::from(quote(&input).into_owned()),
+        }
+    }
+
+    /// takes the next input in order
+    pub fn next_input(&mut self) -> Option<&'a str> {
+        self.take(Slot::Next).map(|idx| self.inputs[idx])
+    }
+
+    /// takes the input filling `slot` and returns its position in the batch
+    pub fn take(&mut self, slot: Slot) -> Option<usize> {
+        let idx = match slot {
+            Slot::Next => self.pos,
+            Slot::Current => self.pos.saturating_sub(1),
+            Slot::Index(idx) => idx,
+            Slot::Seq | Slot::Time => return None,
+        };
+
+        if idx >= self.inputs.len() {
+            return None;
+        }
+
+        if slot == Slot::Next {
+            self.pos += 1;
+        }
+
+        self.used = self.used.max(idx + 1);
+
+        Some(idx)
+    }
+
+    /// the input at `idx` transformed by `modifier`
+    pub fn modify(&self, idx: usize, modifier: &Modifier) -> Cow<'a, str> {
+        modifier.apply(self.inputs[idx], self.docs.get(idx))
+    }
+
+    /// the inputs no placeholder referred to
+    pub fn rest(&self) -> &'b [&'a str] {
+        &self.inputs[self.used..]
+    }
+}
+
+/// finds the first placeholder in `s`
+///
+/// returns the offset, length, slot and modifier of the placeholder.
+/// A placeholder is either the pattern itself, the pattern followed by a
+/// position (`%2`) or a braced position and/or modifier (`{/}`, `{2.}`).
+/// A braced position alone (`{2}`) is only a placeholder with the pattern
+/// `{}`, so arguments like `stash@{1}` are left alone otherwise.
+/// The placeholders of `syntax` are recognised as well.
+fn find_placeholder(
+    pattern: &str,
+    s: &str,
+    syntax: Syntax,
+) -> Option<(usize, usize, Slot, Modifier)> {
+    let mut found = None;
+
+    if let Some(idx) = s.find(pattern) {
+        let after = &s[idx + pattern.len()..];
+        let (len, slot) = match position(after) {
+            Some((digits, n)) => (pattern.len() + digits, Slot::Index(n)),
+            None => (pattern.len(), Slot::Next),
+        };
+
+        found = Some((idx, len, slot, Modifier::None));
+    }
+
+    let mut search = 0;
+    while let Some(idx) = s[search..].find('{').map(|i| i + search) {
+        if found.as_ref().is_some_and(|(f, _, _, _)| *f <= idx) {
+            break;
+        }
+
+        if let Some((len, slot, modifier)) = braced(&s[idx..], pattern == "{}", syntax) {
+            found = Some((idx, len, slot, modifier));
+            break;
+        }
+
+        search = idx + 1;
+    }
+
+    found
+}
+
+/// parses a braced placeholder at the start of `s`
+///
+/// positions without a modifier are only placeholders with `positions` set.
+fn braced(s: &str, positions: bool, syntax: Syntax) -> Option<(usize, Slot, Modifier)> {
+    let close = s.find('}')?;
+    let inner = &s[1..close];
+
+    match inner {
+        "#" if syntax.job => return Some((close + 1, Slot::Seq, Modifier::None)),
+        "time" if syntax.job => return Some((close + 1, Slot::Time, Modifier::None)),
+        _ => (),
+    }
+
+    let (digits, slot) = match position(inner) {
+        Some((digits, n)) => (digits, Slot::Index(n)),
+        None => (0, Slot::Next),
+    };
+
+    let modifier = Modifier::parse(&inner[digits..])?;
+
+    let slot = match (slot, &modifier) {
+        (Slot::Next, Modifier::None) => return None,
+        (Slot::Index(_), Modifier::None) if !positions => return None,
+        (_, Modifier::Field(_)) if !syntax.json => return None,
+        (Slot::Next, _) => Slot::Current,
+        (slot, _) => slot,
+    };
+
+    Some((close + 1, slot, modifier))
+}
+
+/// parses a one based position at the start of `s` into a zero based index
+fn position(s: &str) -> Option<(usize, usize)> {
+    let digits = s.bytes().take_while(|b| b.is_ascii_digit()).count();
+
+    match s[..digits].parse::<usize>() {
+        Ok(n) if n > 0 => Some((digits, n - 1)),
+        _ => None,
+    }
 }
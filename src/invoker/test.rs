@@ -1,3 +1,5 @@
+use super::modifier::Modifier;
+use super::template::Slot;
 use super::*;
 
 #[test]
@@ -6,7 +8,10 @@ fn new() {
         Invoker::new("%", vec!["echo".to_string(), "%".to_string()]),
         Invoker {
             templates: vec![Template::Static("echo".to_string())],
-            offsets: vec![1]
+            offsets: vec![1],
+            shell: None,
+            tag: None,
+            json: false,
         },
         "basic 'echo %' case"
     );
@@ -18,7 +23,10 @@ fn new() {
                 Template::Static("echo".to_string()),
                 Template::Static("%".to_string())
             ],
-            offsets: vec![]
+            offsets: vec![],
+            shell: None,
+            tag: None,
+            json: false,
         },
         "'echo %' with '{{}}' pattern"
     );
@@ -27,7 +35,10 @@ fn new() {
         Invoker::new("{}", vec!["echo".to_string(), "{}".to_string()]),
         Invoker {
             templates: vec![Template::Static("echo".to_string())],
-            offsets: vec![1]
+            offsets: vec![1],
+            shell: None,
+            tag: None,
+            json: false,
         },
         "'echo {{}}' with '{{}}' pattern"
     );
@@ -118,6 +129,8 @@ fn template_new() {
         Template::new("%", "of=%".to_string()),
         Template::Interp {
             offsets: vec![3],
+            slots: vec![Slot::Next],
+            modifiers: vec![Modifier::None],
             base: "of=".to_string(),
         }
     );
@@ -126,6 +139,8 @@ fn template_new() {
         Template::new("%", "%=%".to_string()),
         Template::Interp {
             offsets: vec![0, 1],
+            slots: vec![Slot::Next, Slot::Next],
+            modifiers: vec![Modifier::None, Modifier::None],
             base: "=".to_string(),
         }
     );
@@ -134,6 +149,8 @@ fn template_new() {
         Template::new("%", "%=%xyz".to_string()),
         Template::Interp {
             offsets: vec![0, 1],
+            slots: vec![Slot::Next, Slot::Next],
+            modifiers: vec![Modifier::None, Modifier::None],
             base: "=xyz".to_string(),
         }
     );
@@ -142,23 +159,502 @@ fn template_new() {
 #[test]
 fn template_apply() {
     let template = Template::new("%", "of=%".to_string());
-    let params = vec![&"/dev/null"];
+    let params = vec!["/dev/null"];
 
     assert_eq!(
-        template.apply(&mut params.into_iter()),
+        template.apply(&mut Cursor::new(&params, false)),
         "of=/dev/null".to_string()
     );
 
     let template = Template::new("%", "%=%".to_string());
-    let params = vec![&"if", &"/dev/null"];
+    let params = vec!["if", "/dev/null"];
 
     assert_eq!(
-        template.apply(&mut params.into_iter()),
+        template.apply(&mut Cursor::new(&params, false)),
         "if=/dev/null".to_string()
     );
 
     let template = Template::new("%", "%=%".to_string());
-    let params = vec![&"if"];
+    let params = vec!["if"];
 
-    assert_eq!(template.apply(&mut params.into_iter()), "if=".to_string());
+    assert_eq!(
+        template.apply(&mut Cursor::new(&params, false)),
+        "if=".to_string()
+    );
+}
+
+#[test]
+fn template_positional() {
+    assert_eq!(
+        Template::new("{}", "{2}-{}1".to_string()),
+        Template::Interp {
+            offsets: vec![0, 1],
+            slots: vec![Slot::Index(1), Slot::Index(0)],
+            modifiers: vec![Modifier::None, Modifier::None],
+            base: "-".to_string(),
+        },
+        "braced and pattern positions"
+    );
+
+    assert_eq!(
+        Template::new("%", "stash@{1}".to_string()),
+        Template::Static("stash@{1}".to_string()),
+        "braced positions are left alone with other patterns"
+    );
+
+    assert_eq!(
+        Template::new("%", "{0}{x}%0".to_string()),
+        Template::Interp {
+            offsets: vec![6],
+            slots: vec![Slot::Next],
+            modifiers: vec![Modifier::None],
+            base: "{0}{x}0".to_string(),
+        },
+        "non positions are left alone"
+    );
+
+    let template = Template::new("%", "%2=%1:%2".to_string());
+    let params = vec!["key", "value"];
+
+    assert_eq!(
+        template.apply(&mut Cursor::new(&params, false)),
+        "value=key:value".to_string(),
+        "reordered and reused"
+    );
+}
+
+#[test]
+fn positional_previews() {
+    let mv = Invoker::new(
+        "%",
+        vec!["mv".to_string(), "%2".to_string(), "%1".to_string()],
+    );
+
+    assert_eq!(mv.slots(), 2, "highest position sets the batch size");
+
+    assert_eq!(
+        mv.preview(&["a", "b"]).as_strs(),
+        vec!["mv", "b", "a"],
+        "reordered fill"
+    );
+
+    assert_eq!(
+        mv.preview(&["a", "b", "c"]).as_strs(),
+        vec!["mv", "b", "a", "c"],
+        "unreferenced inputs are appended"
+    );
+
+    assert_eq!(
+        mv.preview(&["a"]).as_strs(),
+        vec!["mv", "", "a"],
+        "missing positions are empty"
+    );
+
+    let cp = Invoker::new(
+        "%",
+        vec![
+            "cp".to_string(),
+            "%".to_string(),
+            "%1.bak".to_string(),
+            "%3".to_string(),
+        ],
+    );
+
+    assert_eq!(cp.slots(), 3);
+
+    assert_eq!(
+        cp.preview(&["a", "b", "c"]).as_strs(),
+        vec!["cp", "a", "a.bak", "c"],
+        "mixed sequential and positional"
+    );
+}
+
+#[test]
+fn modifiers() {
+    let path = "dir/sub/file.tar.gz";
+
+    assert_eq!(
+        Modifier::Basename.apply(path, None),
+        "file.tar.gz",
+        "basename"
+    );
+    assert_eq!(
+        Modifier::Basename.apply("file", None),
+        "file",
+        "bare basename"
+    );
+
+    assert_eq!(Modifier::Dirname.apply(path, None), "dir/sub", "dirname");
+    assert_eq!(Modifier::Dirname.apply("file", None), ".", "bare dirname");
+    assert_eq!(Modifier::Dirname.apply("/file", None), "/", "root dirname");
+
+    assert_eq!(
+        Modifier::StripExt.apply(path, None),
+        "dir/sub/file.tar",
+        "strip ext"
+    );
+    assert_eq!(
+        Modifier::StripExt.apply("dir.d/file", None),
+        "dir.d/file",
+        "strip ext ignores dots in directories"
+    );
+    assert_eq!(
+        Modifier::StripExt.apply("dir/.bashrc", None),
+        "dir/.bashrc",
+        "strip ext ignores hidden files"
+    );
+
+    assert_eq!(
+        Modifier::BasenameStripExt.apply(path, None),
+        "file.tar",
+        "basename strip ext"
+    );
+
+    assert_eq!(Modifier::Ext.apply(path, None), "gz", "ext");
+    assert_eq!(Modifier::Ext.apply("dir.d/file", None), "", "missing ext");
+}
+
+#[test]
+fn template_modifiers() {
+    assert_eq!(
+        Template::new("%", "{/}:{2//}:{.}:{/.}:{ext}".to_string()),
+        Template::Interp {
+            offsets: vec![0, 1, 2, 3, 4],
+            slots: vec![
+                Slot::Current,
+                Slot::Index(1),
+                Slot::Current,
+                Slot::Current,
+                Slot::Current
+            ],
+            modifiers: vec![
+                Modifier::Basename,
+                Modifier::Dirname,
+                Modifier::StripExt,
+                Modifier::BasenameStripExt,
+                Modifier::Ext
+            ],
+            base: "::::".to_string(),
+        }
+    );
+
+    assert_eq!(
+        Template::new("%", "{}{foo}{/x}".to_string()),
+        Template::Static("{}{foo}{/x}".to_string()),
+        "unknown modifiers are left alone"
+    );
+
+    let convert = Invoker::new(
+        "%",
+        vec![
+            "convert".to_string(),
+            "%".to_string(),
+            "out/{/.}.png".to_string(),
+        ],
+    );
+
+    assert_eq!(convert.slots(), 1);
+
+    assert_eq!(
+        convert.preview(&["img/a.jpg"]).as_strs(),
+        vec!["convert", "img/a.jpg", "out/a.png"],
+        "modifiers apply to the input of the bare placeholder"
+    );
+
+    let cp = Invoker::new(
+        "%",
+        vec![
+            "cp".to_string(),
+            "{.}.txt".to_string(),
+            "%".to_string(),
+            "%".to_string(),
+            "{/}".to_string(),
+        ],
+    );
+
+    assert_eq!(cp.slots(), 2);
+
+    assert_eq!(
+        cp.preview(&["a.md", "dir/b.md"]).as_strs(),
+        vec!["cp", "a.txt", "a.md", "dir/b.md", "b.md"],
+        "before any bare placeholder and after later ones"
+    );
+
+    let gzip = Invoker::new("%", vec!["gzip".to_string(), "{/}".to_string()]);
+
+    assert_eq!(gzip.slots(), 1, "modifiers alone take one input");
+
+    let mv = Invoker::new(
+        "%",
+        vec![
+            "mv".to_string(),
+            "%1".to_string(),
+            "{1//}/{1/.}.{1ext}.bak".to_string(),
+        ],
+    );
+
+    assert_eq!(mv.slots(), 1);
+
+    assert_eq!(
+        mv.preview(&["src/main.rs"]).as_strs(),
+        vec!["mv", "src/main.rs", "src/main.rs.bak"],
+        "positional modifiers"
+    );
+}
+
+#[test]
+fn field_modifiers() {
+    let record = r#"{"user":{"id":7,"name":"ada"},"files":["a.txt","b.txt"],"gone":null}"#;
+    let doc: Value = serde_json::from_str(record).unwrap();
+
+    assert_eq!(
+        Modifier::parse(".user.id")
+            .unwrap()
+            .apply(record, Some(&doc)),
+        "7",
+        "nested number"
+    );
+
+    assert_eq!(
+        Modifier::parse(".files[1]")
+            .unwrap()
+            .apply(record, Some(&doc)),
+        "b.txt",
+        "array index"
+    );
+
+    assert_eq!(
+        Modifier::parse(".user").unwrap().apply(record, Some(&doc)),
+        r#"{"id":7,"name":"ada"}"#,
+        "objects are compact json"
+    );
+
+    assert_eq!(
+        Modifier::parse(".gone").unwrap().apply(record, Some(&doc)),
+        "",
+        "null is empty"
+    );
+
+    assert_eq!(
+        Modifier::parse(".missing.key")
+            .unwrap()
+            .apply(record, Some(&doc)),
+        "",
+        "missing is empty"
+    );
+
+    assert_eq!(Modifier::parse(".a..b"), None, "empty key");
+    assert_eq!(Modifier::parse(".a[x]"), None, "bad index");
+    assert_eq!(Modifier::parse(".a[0]x"), None, "trailing garbage");
+
+    let command = vec![
+        "chown".to_string(),
+        "{.user.name}".to_string(),
+        "{.files[0]}".to_string(),
+    ];
+
+    let invoker = Invoker::parse("%", command.clone(), true);
+
+    assert_eq!(invoker.slots(), 1, "fields refer to one record");
+
+    assert_eq!(
+        invoker.preview_json(&[record], &[doc]).as_strs(),
+        vec!["chown", "ada", "a.txt"]
+    );
+
+    assert_eq!(
+        Invoker::new("%", command).preview(&["x"]).as_strs(),
+        vec!["chown", "{.user.name}", "{.files[0]}", "x"],
+        "fields are only known to json inputs"
+    );
+
+    let pair = Invoker::parse(
+        "%",
+        vec![
+            "echo".to_string(),
+            "{.id}".to_string(),
+            "{2.id}".to_string(),
+        ],
+        true,
+    );
+    let records = [r#"{"id":1}"#, r#"{"id":2}"#];
+    let docs: Vec<Value> = records
+        .iter()
+        .map(|r| serde_json::from_str(r).unwrap())
+        .collect();
+
+    assert_eq!(
+        pair.preview_json(&records, &docs).as_strs(),
+        vec!["echo", "1", "2"],
+        "fields of every record in a batch"
+    );
+}
+
+#[test]
+fn shell_quote() {
+    assert_eq!(quote::quote("plain/path-1.txt"), "plain/path-1.txt");
+    assert_eq!(quote::quote(""), "''");
+    assert_eq!(quote::quote("two words"), "'two words'");
+    assert_eq!(quote::quote("it's"), "'it'\\''s'");
+    assert_eq!(quote::quote("$(rm -rf ~)"), "'$(rm -rf ~)'");
+}
+
+#[test]
+fn shell_previews() {
+    let invoker = Invoker::new("%", vec!["grep -c foo %1 | tee {1/.}.count".to_string()])
+        .with_shell(Some("sh".to_string()));
+
+    assert_eq!(
+        invoker.preview(&["dir/my file.txt"]).as_strs(),
+        vec![
+            "sh",
+            "-c",
+            "grep -c foo 'dir/my file.txt' | tee 'my file'.count"
+        ],
+        "quoted substitutions"
+    );
+
+    let invoker = Invoker::new(
+        "%",
+        vec![
+            "echo".to_string(),
+            "%".to_string(),
+            "&&".to_string(),
+            "true".to_string(),
+        ],
+    )
+    .with_shell(Some("bash".to_string()));
+
+    assert_eq!(
+        invoker.preview(&["a;b", "c d"]).as_strs(),
+        vec!["bash", "-c", "echo 'a;b' && true 'c d'"],
+        "quoted whole arguments and overfill"
+    );
+}
+
+#[test]
+fn display_escape() {
+    assert_eq!(quote::escape("plain"), "plain");
+    assert_eq!(quote::escape("a b"), "'a b'");
+    assert_eq!(quote::escape("line\nbreak"), "$'line\\nbreak'");
+    assert_eq!(quote::escape("it's\t\x1b"), "$'it\\'s\\t\\x1b'");
+
+    let invoker = Invoker::new("%", vec!["echo".to_string(), "%".to_string()]);
+
+    assert_eq!(
+        invoker.preview(&["a b", "c'd", "e\nf"]).to_string(),
+        "echo 'a b' 'c'\\''d' $'e\\nf'"
+    );
+
+    assert_eq!(
+        invoker.preview(&["a b"]).detach().to_string(),
+        "echo 'a b'",
+        "detached previews display the same"
+    );
+}
+
+#[test]
+fn tags() {
+    let command = vec!["echo".to_string(), "%".to_string()];
+
+    let invoker = Invoker::new("%", command.clone()).with_tag(true, "%", None);
+    let preview = invoker.preview(&["a", "b c"]);
+
+    assert_eq!(preview.tag().unwrap().render(1), "a b c\t", "inputs");
+    assert!(
+        preview.detach().tag().is_some(),
+        "detached previews keep the tag"
+    );
+
+    let invoker =
+        Invoker::new("%", command.clone()).with_tag(false, "%", Some("{#} {2/} [%]".to_string()));
+
+    assert_eq!(
+        invoker.preview(&["a", "x/y"]).tag().unwrap().render(7),
+        "7 y [a]\t",
+        "template"
+    );
+
+    let invoker =
+        Invoker::new("%", command.clone()).with_tag(false, "%", Some("{time}".to_string()));
+    let tag = invoker.preview(&["a"]).tag().unwrap().render(1);
+
+    assert_eq!(tag.len(), "2001-09-09T01:46:40Z\t".len(), "timestamp");
+
+    let invoker = Invoker::new("%", vec!["echo".to_string(), "{#}".to_string()]);
+
+    assert_eq!(
+        invoker.preview(&["a"]).as_strs(),
+        vec!["echo", "{#}", "a"],
+        "job placeholders are only known to tags"
+    );
+    assert!(invoker.preview(&["a"]).tag().is_none());
+
+    assert_eq!(
+        tag::timestamp(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000)),
+        "2001-09-09T01:46:40Z"
+    );
+    assert_eq!(
+        tag::timestamp(std::time::UNIX_EPOCH + std::time::Duration::from_secs(951_782_400)),
+        "2000-02-29T00:00:00Z",
+        "leap day"
+    );
+}
+
+#[test]
+fn command_sizes() {
+    let size = |invoker: &Invoker, inputs: &[&str]| -> Size {
+        inputs
+            .iter()
+            .fold(invoker.base_size(), |size, i| size + invoker.input_size(i))
+    };
+
+    // xargs counts the arguments with their terminating nulls
+    let exact = |invoker: &Invoker, inputs: &[&str]| -> Size {
+        let args = invoker.preview(inputs);
+        let args = args.as_strs();
+
+        Size {
+            chars: args.iter().map(|a| a.len() + 1).sum(),
+            args: args.len(),
+        }
+    };
+
+    let echo = Invoker::new("%", vec!["echo".to_string()]);
+    let inputs = ["aaaa", "bbbb", "cccc"];
+
+    assert_eq!(
+        size(&echo, &inputs),
+        Size { chars: 20, args: 4 },
+        "like xargs"
+    );
+
+    let invoker = Invoker::new("%", vec!["rm".to_string(), "-f".to_string()]);
+    let inputs = ["a.txt", "some dir/b.txt", ""];
+
+    assert_eq!(
+        size(&invoker, &inputs),
+        exact(&invoker, &inputs),
+        "arguments"
+    );
+
+    let invoker = invoker.with_shell(Some("sh".to_string()));
+
+    assert_eq!(
+        size(&invoker, &inputs),
+        exact(&invoker, &inputs),
+        "quoted script"
+    );
+}
+
+#[test]
+fn group_keys() {
+    assert_eq!(Key::new("%", "{//}").of(&["dir/a.txt"]), "dir", "template");
+    assert_eq!(Key::new("%", "2").of(&["x", "y"]), "y", "column number");
+    assert_eq!(
+        Key::new("%", "{.id}").of(&[r#"{"id": 7}"#]),
+        "{.id}",
+        "fields are only known to json inputs"
+    );
+    assert_eq!(Key::new("%", "%").of(&["a b"]), "a b", "whole input");
 }
@@ -1,28 +1,44 @@
 use std::fs::File;
-use std::io::BufRead;
+use std::time::Instant;
 use structopt::StructOpt;
 
 mod cli;
 mod collector;
+mod duration;
 mod invoker;
 mod proc;
+mod size;
 mod source;
 
-use cli::Cli;
-use collector::{Collector, Limit};
-use invoker::Invoker;
-use proc::Process;
-use source::Source;
+use cli::{Cli, OnInvalid};
+use collector::{Collector, Groups, Limit};
+use invoker::{Invoker, Key, Preview, Size};
+use proc::{Process, Resume};
+use source::{Feed, Next, Source};
 
 fn main() {
     let cli = Cli::from_args().fill_parallel();
     cli.validate();
 
+    // read before the job log is opened for new records
+    let resume = match (&cli.joblog, cli.resuming()) {
+        (Some(path), true) => match Resume::read(path, cli.resume_failed) {
+            Ok(resume) => Some(resume),
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+
     let processor = proc::process(&cli);
 
-    let invoker = Invoker::new(&cli.pattern, cli.command);
+    let invoker = Invoker::parse(&cli.pattern, cli.command.clone(), cli.jsonl)
+        .with_shell(cli.shell())
+        .with_tag(cli.tag, &cli.pattern, cli.tagstring.clone());
 
-    let mut src = match cli.args {
+    let mut src = match &cli.args {
         Some(p) => File::open(p).unwrap().into(),
         None => Source::default(),
     };
@@ -30,33 +46,173 @@ fn main() {
     let limit = match (cli.join, cli.lines) {
         (true, _) => Limit::Unlimited,
         (_, Some(s)) => s.into(),
+        (_, None) if cli.group_by.is_some() => Limit::Unlimited,
+        (_, None) if cli.columns() => 1.into(),
+        // json records run one at a time unless positions refer to more
+        (_, None) if cli.jsonl => invoker.slots().max(1).into(),
         (_, None) => invoker.slots().into(),
     };
 
-    let mut collector = Collector::new(limit);
+    let collector = Collector::new(limit)
+        .with_limits(invoker.base_size(), cli.max_chars, cli.arg_max())
+        .with_timeout(cli.batch_timeout);
+
+    let key = cli.group_by.as_ref().map(|key| Key::new(&cli.pattern, key));
+    let mut groups = Groups::new(collector, cli.sorted);
+    let mut seq = 0;
+
+    let positions = invoker.positions();
+
+    let mut run = |collector: &mut Collector| {
+        let inputs = collector.refs();
+
+        if inputs.len() < positions {
+            eprintln!(
+                "job {}: no input at position {} (the batch has {})",
+                seq + 1,
+                positions,
+                inputs.len()
+            );
+            std::process::exit(1);
+        }
+
+        let preview = invoker.preview_json(&inputs, collector.docs());
+        dispatch(&processor, resume.as_ref(), seq, &preview);
+        collector.clear();
+        seq += 1;
+    };
+
+    if cli.pipe {
+        let chunks = src
+            .buffer()
+            .chunks(&cli.record_delimiter(), cli.cut(), cli.recstart.clone());
+
+        for (seq, chunk) in chunks.enumerate() {
+            if processor.halted() {
+                break;
+            }
+
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let preview = invoker.preview(&[]).with_input(chunk);
+            dispatch(&processor, resume.as_ref(), seq, &preview);
+        }
+
+        std::process::exit(processor.finalize().code());
+    }
+
+    // reading on a thread lets a partial batch run while input is slow
+    let mut feed = match cli.batch_timeout {
+        Some(_) => Feed::spawn(src, cli.format()),
+        None => Feed::Inputs(src.buffer().inputs(cli.format())),
+    };
+
+    let mut num = 0;
+
+    loop {
+        if processor.halted() {
+            break;
+        }
+
+        // partial batches due to run
+        if let Some(now) = groups.deadline().map(|_| Instant::now()) {
+            for batch in groups.batches() {
+                if batch.deadline().is_some_and(|at| at <= now) {
+                    run(batch);
+                }
+            }
+        }
 
-    let source = src.buffer();
+        let record = match feed.next(groups.deadline()) {
+            Next::Item(record) => record,
+            Next::Timeout => continue,
+            Next::End => break,
+        };
+
+        num += 1;
+
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+
+        let record = match source::decode(record, cli.jsonl) {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!("record {}: {}", num, e);
+
+                match cli.on_invalid {
+                    OnInvalid::Skip => continue,
+                    OnInvalid::Fail => std::process::exit(1),
+                }
+            }
+        };
+
+        if let Some(key) = &key {
+            let refs: Vec<&str> = record.columns.iter().map(|c| c.as_str()).collect();
+
+            if let Some(mut ended) = groups.select(key.of(&refs)) {
+                run(&mut ended);
+            }
+        }
 
-    let splits = source.split(if cli.null_separated { 0x0 } else { 0xA });
+        let collector = groups.current();
+        let size = record
+            .columns
+            .iter()
+            .map(|c| invoker.input_size(c))
+            .fold(Size::default(), |total, size| total + size);
 
-    for split in splits {
-        let split = split.unwrap();
+        if !collector.fits(size) {
+            if collector.is_empty() {
+                eprintln!("record {}: command line too long", num);
+                std::process::exit(1);
+            }
 
-        let input = String::from_utf8(split).unwrap();
+            run(collector);
+        }
 
-        collector.push(input);
+        collector.push(record, size);
 
         if collector.full() {
-            let preview = invoker.preview(&collector.refs());
-            processor.process(&preview);
-            collector.clear();
+            run(collector);
         }
     }
 
-    if !collector.is_empty() {
-        let preview = invoker.preview(&collector.refs());
-        processor.process(&preview);
+    for batch in groups.batches() {
+        if !batch.is_empty() && !processor.halted() {
+            run(batch);
+        }
     }
 
-    processor.finalize();
+    let summary = processor.finalize();
+
+    std::process::exit(summary.code());
+}
+
+/// runs job `seq` unless an earlier run already did
+fn dispatch<P: Process>(processor: &P, resume: Option<&Resume>, seq: usize, preview: &Preview) {
+    let done = match resume.map(|r| r.done(seq, &preview.to_string())) {
+        Some(Ok(done)) => done,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        None => false,
+    };
+
+    if done {
+        processor.skip(seq);
+    } else {
+        processor.process(seq, preview);
+    }
 }
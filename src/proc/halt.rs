@@ -0,0 +1,87 @@
+use super::Summary;
+
+use std::str::FromStr;
+
+/// jobs that must complete before a percentage can halt
+const MIN_JOBS: u32 = 3;
+
+/// When to stop running commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Halt {
+    pub when: When,
+    pub trigger: Trigger,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum When {
+    /// start no new commands but let running ones finish
+    Soon,
+    /// start no new commands and terminate running ones
+    Now,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Fail(Threshold),
+    Success(Threshold),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+    Count(u32),
+    /// percentage of the commands completed so far, once a few completed
+    Percent(u32),
+}
+
+impl Halt {
+    /// whether the commands summarized should stop further ones
+    pub fn triggered(&self, summary: &Summary) -> bool {
+        let (threshold, count) = match self.trigger {
+            Trigger::Fail(threshold) => (threshold, summary.unsuccessful()),
+            Trigger::Success(threshold) => (threshold, summary.jobs - summary.unsuccessful()),
+        };
+
+        match threshold {
+            Threshold::Count(n) => count >= n,
+            Threshold::Percent(p) => summary.jobs >= MIN_JOBS && count * 100 >= p * summary.jobs,
+        }
+    }
+}
+
+impl FromStr for Halt {
+    type Err = String;
+
+    /// parses `<soon|now>,<fail|success>=<n|n%>`
+    fn from_str(s: &str) -> Result<Halt, String> {
+        let invalid = || format!("expected '<soon|now>,<fail|success>=<n|n%>', found '{}'", s);
+
+        let (when, trigger) = s.split_once(',').ok_or_else(invalid)?;
+
+        let when = match when {
+            "soon" => When::Soon,
+            "now" => When::Now,
+            _ => return Err(invalid()),
+        };
+
+        let (kind, threshold) = trigger.split_once('=').ok_or_else(invalid)?;
+
+        let threshold = match threshold.strip_suffix('%') {
+            Some(p) => match p.parse() {
+                Ok(p) if p > 0 && p <= 100 => Threshold::Percent(p),
+                _ => return Err(invalid()),
+            },
+            None => match threshold.parse() {
+                Ok(n) if n > 0 => Threshold::Count(n),
+                _ => return Err(invalid()),
+            },
+        };
+
+        let trigger = match kind {
+            "fail" => Trigger::Fail(threshold),
+            "success" => Trigger::Success(threshold),
+            _ => return Err(invalid()),
+        };
+
+        Ok(Halt { when, trigger })
+    }
+}
@@ -5,20 +5,49 @@ use std::io;
 
 pub struct InterRun {
     tty: File,
+    tracker: Tracker,
 }
 
 impl InterRun {
-    pub fn new() -> io::Result<InterRun> {
+    pub fn new(tracker: Tracker) -> io::Result<InterRun> {
         let tty = OpenOptions::new().write(true).read(true).open("/dev/tty")?;
 
-        Ok(InterRun { tty })
+        Ok(InterRun { tty, tracker })
     }
 }
 
 impl Process for InterRun {
-    fn process(&self, preview: &Preview) {
-        preview.run_interactive(&self.tty).unwrap();
+    fn process(&self, seq: usize, preview: &Preview) {
+        if self.tracker.halted() {
+            return;
+        }
+
+        let job = Job {
+            seq,
+            slot: 1,
+            tag: preview.tag(),
+            input: preview.input().map(Input::Chunk),
+            display: preview,
+        };
+
+        let command = || {
+            let mut command = preview.command();
+            command.stdin(self.tty.try_clone().unwrap());
+            command
+        };
+
+        self.tracker.run_retrying(&job, command);
+    }
+
+    fn skip(&self, seq: usize) {
+        self.tracker.skip(seq);
+    }
+
+    fn halted(&self) -> bool {
+        self.tracker.halted()
     }
 
-    fn finalize(self) {}
+    fn finalize(self) -> Summary {
+        self.tracker.summary()
+    }
 }
@@ -0,0 +1,132 @@
+use super::status::{Attempt, Job, Outcome};
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+const HEADER: &str = "Seq\tSlot\tStarttime\tJobRuntime\tExitval\tSignal\tCommand\n";
+
+/// Tab separated record of every job run
+///
+/// each record is written with a single write so the log is complete
+/// up to the last finished job even if yargs itself is killed.
+pub struct JobLog {
+    file: Mutex<File>,
+}
+
+impl JobLog {
+    /// creates the log at `path`, replacing an existing one
+    pub fn create(path: &Path) -> io::Result<JobLog> {
+        let mut file = File::create(path)?;
+
+        file.write_all(HEADER.as_bytes())?;
+
+        Ok(JobLog {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// opens the log at `path` to add records to it
+    pub fn append(path: &Path) -> io::Result<JobLog> {
+        let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+
+        if file.metadata()?.len() == 0 {
+            file.write_all(HEADER.as_bytes())?;
+        }
+
+        Ok(JobLog {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn write(&self, job: &Job, attempt: &Attempt) -> io::Result<()> {
+        let (exit, signal) = exit_signal(attempt.outcome);
+        let start = attempt
+            .start
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+
+        let record = format!(
+            "{}\t{}\t{:.3}\t{:.3}\t{}\t{}\t{}\n",
+            job.seq + 1,
+            job.slot,
+            start,
+            attempt.runtime.as_secs_f64(),
+            exit,
+            signal,
+            job.display
+        );
+
+        self.file.lock().unwrap().write_all(record.as_bytes())
+    }
+}
+
+/// Jobs an earlier run recorded, to continue where it stopped
+pub struct Resume {
+    /// whether jobs recorded as failed only are skipped
+    failed: bool,
+    /// whether the last record of a job succeeded and its command
+    jobs: HashMap<usize, (bool, String)>,
+}
+
+impl Resume {
+    /// reads the log at `path`, a missing log has no jobs
+    ///
+    /// with `failed` set jobs that failed are run again.
+    pub fn read(path: &Path, failed: bool) -> io::Result<Resume> {
+        let log = match std::fs::read_to_string(path) {
+            Ok(log) => log,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut jobs = HashMap::new();
+
+        for line in log.lines().skip(1) {
+            let fields: Vec<&str> = line.splitn(7, '\t').collect();
+
+            if let [seq, _, _, _, exit, signal, command] = fields[..] {
+                if let Ok(seq) = seq.parse::<usize>() {
+                    let success = exit == "0" && signal == "0";
+                    jobs.insert(seq, (success, command.to_string()));
+                }
+            }
+        }
+
+        Ok(Resume { failed, jobs })
+    }
+
+    /// whether job `seq` running `command` was already run
+    ///
+    /// fails when the recorded command differs, as the input changed.
+    pub fn done(&self, seq: usize, command: &str) -> Result<bool, String> {
+        match self.jobs.get(&(seq + 1)) {
+            Some((_, recorded)) if recorded != command => Err(format!(
+                "job {} was '{}' but is now '{}', the input changed",
+                seq + 1,
+                recorded,
+                command
+            )),
+            Some((success, _)) => Ok(*success || !self.failed),
+            None => Ok(false),
+        }
+    }
+}
+
+/// the exit value and signal logged for an outcome
+///
+/// commands that timed out are logged like coreutils' timeout reports them,
+/// commands that could not be started like a shell does.
+fn exit_signal(outcome: Outcome) -> (i32, i32) {
+    match outcome {
+        Outcome::Exited(code) => (code, 0),
+        Outcome::Signaled(signal) => (-1, signal),
+        Outcome::TimedOut => (124, 0),
+        Outcome::NotExecutable => (126, 0),
+        Outcome::NotFound => (127, 0),
+    }
+}
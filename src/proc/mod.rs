@@ -2,39 +2,107 @@ use crate::invoker::Preview;
 use crate::Cli;
 use std::fs::OpenOptions;
 
+mod halt;
 mod inter;
+mod joblog;
+mod output;
 mod par;
+mod prompt;
+mod robin;
 mod run;
+mod status;
+mod timeout;
 
 mod trace;
 
+#[cfg(test)]
+mod test;
+
+pub use halt::Halt;
 pub use inter::InterRun;
+pub use joblog::{JobLog, Resume};
+pub use output::Mode;
 pub use par::ParRun;
+pub use prompt::Prompt;
+pub use robin::RoundRobin;
 pub use run::Run;
+pub use status::{Input, Job, Policy, Summary, Tracker};
+pub use timeout::Timeout;
 
 pub use trace::Log;
 
 pub trait Process: Sized {
-    fn process(&self, preview: &Preview);
-    fn finalize(self);
+    /// runs job `seq`, jobs are numbered in input order
+    fn process(&self, seq: usize, preview: &Preview);
+    /// passes over job `seq` without running it
+    fn skip(&self, seq: usize);
+    /// whether commands are traced as they start instead of per job
+    fn traces(&self) -> bool {
+        false
+    }
+    /// whether the halt policy stopped further commands
+    fn halted(&self) -> bool;
+    fn finalize(self) -> Summary;
 }
 
 pub enum URun {
     Inter(InterRun),
     Base(Run),
     Parallel(ParRun),
+    RoundRobin(RoundRobin),
 }
 
 pub fn process(cli: &Cli) -> Log<URun> {
+    if cli.dry_run {
+        return Log::DryRun;
+    }
+
+    let tracker = Tracker::new(Policy {
+        halt: cli.halt,
+        timeout: cli.timeout,
+        kill_after: cli.kill_after,
+        retries: cli.retries,
+        retry_delay: cli.retry_delay,
+        retry_backoff: cli.retry_backoff,
+        trace: cli.trace,
+        output: cli.output_mode(),
+        keep_order: cli.keep_order,
+        tty: cli.tty,
+    });
+
+    tracker.forward_signals();
+
+    let tracker = match &cli.joblog {
+        Some(path) => {
+            let joblog = if cli.resuming() {
+                JobLog::append(path)
+            } else {
+                JobLog::create(path)
+            };
+
+            match joblog {
+                Ok(joblog) => tracker.with_joblog(joblog),
+                Err(e) => {
+                    eprintln!("{}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => tracker,
+    };
+
+    let writer = tracker.writer();
+
     let run = match cli.parallel {
+        p if cli.round_robin => URun::RoundRobin(RoundRobin::new(p.unwrap_or(1), tracker)),
         None | Some(1) => {
             if cli.tty {
-                URun::Inter(InterRun::new().unwrap())
+                URun::Inter(InterRun::new(tracker).unwrap())
             } else {
-                URun::Base(Run)
+                URun::Base(Run::new(tracker))
             }
         }
-        Some(p) => URun::Parallel(ParRun::new(p)),
+        Some(p) => URun::Parallel(ParRun::new(p, tracker)),
     };
 
     if cli.ask {
@@ -43,7 +111,7 @@ pub fn process(cli: &Cli) -> Log<URun> {
             .write(true)
             .open("/dev/tty")
             .unwrap();
-        Log::Ask(tty, run)
+        Log::Ask(Prompt::new(tty, writer).unwrap(), run)
     } else if cli.trace {
         Log::Trace(run)
     } else {
@@ -52,19 +120,48 @@ pub fn process(cli: &Cli) -> Log<URun> {
 }
 
 impl Process for URun {
-    fn process(&self, preview: &Preview) {
+    fn process(&self, seq: usize, preview: &Preview) {
+        match self {
+            URun::Inter(run) => run.process(seq, preview),
+            URun::Base(run) => run.process(seq, preview),
+            URun::Parallel(run) => run.process(seq, preview),
+            URun::RoundRobin(run) => run.process(seq, preview),
+        }
+    }
+
+    fn skip(&self, seq: usize) {
+        match self {
+            URun::Inter(run) => run.skip(seq),
+            URun::Base(run) => run.skip(seq),
+            URun::Parallel(run) => run.skip(seq),
+            URun::RoundRobin(run) => run.skip(seq),
+        }
+    }
+
+    fn traces(&self) -> bool {
+        match self {
+            URun::Inter(run) => run.traces(),
+            URun::Base(run) => run.traces(),
+            URun::Parallel(run) => run.traces(),
+            URun::RoundRobin(run) => run.traces(),
+        }
+    }
+
+    fn halted(&self) -> bool {
         match self {
-            URun::Inter(run) => run.process(preview),
-            URun::Base(run) => run.process(preview),
-            URun::Parallel(run) => run.process(preview),
+            URun::Inter(run) => run.halted(),
+            URun::Base(run) => run.halted(),
+            URun::Parallel(run) => run.halted(),
+            URun::RoundRobin(run) => run.halted(),
         }
     }
 
-    fn finalize(self) {
+    fn finalize(self) -> Summary {
         match self {
             URun::Inter(run) => run.finalize(),
             URun::Base(run) => run.finalize(),
             URun::Parallel(run) => run.finalize(),
+            URun::RoundRobin(run) => run.finalize(),
         }
     }
 }
@@ -0,0 +1,539 @@
+use std::collections::BTreeMap;
+use std::fs::{remove_file, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{spawn, JoinHandle};
+
+/// Size above which buffered output is moved to a temporary file
+///
+/// applies to the output of a single stream and to all output held back
+/// by a writer.
+const SPILL_SIZE: usize = 1 << 20;
+
+/// How the output of commands reaches yargs' stdout and stderr
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// commands write directly, output of parallel commands may interleave
+    #[default]
+    Ungroup,
+    /// output of a command is written at once when it finishes
+    Group,
+    /// output of parallel commands interleaves by whole lines
+    LineBuffer,
+}
+
+/// Serializes writes to stdout and stderr across workers
+///
+/// When keeping order, output of a job is held back until the output of
+/// all jobs with a lower sequence number was written.
+#[derive(Clone, Default)]
+pub struct Writer(Arc<Mutex<Order>>);
+
+/// Holds back output written through a writer until dropped
+pub struct Hold(Writer);
+
+#[derive(Default)]
+struct Order {
+    keep: bool,
+    /// sequence number of the job whose output is written next
+    next: usize,
+    /// output of later jobs and whether they are complete
+    waiting: BTreeMap<usize, (Vec<(Held, Held)>, bool)>,
+    /// whether output is held back, e.g. while prompting
+    held: bool,
+    /// output held back in the order it was written
+    deferred: Vec<(Held, Held)>,
+    /// bytes of held back output in memory
+    memory: usize,
+    /// file held back output is moved to once too much is in memory
+    spill: Option<File>,
+    /// end of the output in the spill file
+    spilled: u64,
+}
+
+/// Output held back by a writer
+enum Held {
+    /// counted against the memory of the writer
+    Memory(Vec<u8>),
+    /// a range of the spill file of the writer
+    Region { offset: u64, len: u64 },
+    /// output that could not be spilled
+    Buffer(Buffer),
+}
+
+/// Prefixes lines of a stream read in chunks
+struct Prefixer {
+    prefix: Arc<String>,
+    line_start: bool,
+}
+
+/// Output collected from a stream, in memory or spilled to a file
+pub enum Buffer {
+    Memory(Vec<u8>),
+    Spilled(File),
+}
+
+/// Output of a running command being collected
+pub struct Capture {
+    seq: usize,
+    stdout: Option<JoinHandle<Buffer>>,
+    stderr: Option<JoinHandle<Buffer>>,
+    writer: Writer,
+}
+
+#[derive(Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Mode {
+    /// sets up where a command writes to
+    pub fn configure(&self, command: &mut Command) {
+        if *self != Mode::Ungroup {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+    }
+
+    /// starts collecting the output of the spawned child of job `seq`
+    ///
+    /// every line of captured output is prefixed with `prefix`.
+    pub fn capture(
+        &self,
+        seq: usize,
+        child: &mut Child,
+        writer: &Writer,
+        prefix: Option<String>,
+    ) -> Capture {
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let prefix = Arc::new(prefix.unwrap_or_default());
+
+        let (stdout, stderr) = match self {
+            Mode::Ungroup => (None, None),
+            Mode::Group => (
+                stdout.map(|out| buffer(out, prefix.clone())),
+                stderr.map(|err| buffer(err, prefix.clone())),
+            ),
+            Mode::LineBuffer => (
+                stdout.map(|out| lines(out, Stream::Stdout, prefix.clone(), writer.clone())),
+                stderr.map(|err| lines(err, Stream::Stderr, prefix.clone(), writer.clone())),
+            ),
+        };
+
+        Capture {
+            seq,
+            stdout,
+            stderr,
+            writer: writer.clone(),
+        }
+    }
+}
+
+impl Writer {
+    pub fn new(keep_order: bool) -> Writer {
+        Writer(Arc::new(Mutex::new(Order {
+            keep: keep_order,
+            ..Order::default()
+        })))
+    }
+
+    /// writes the output of job `seq` without other output in between
+    pub fn write(&self, seq: usize, out: Buffer, err: Buffer) {
+        let mut order = self.0.lock().unwrap();
+
+        if !order.keep || seq == order.next {
+            order.emit(out, err);
+        } else {
+            let held = (order.park(out), order.park(err));
+            order.waiting.entry(seq).or_default().0.push(held);
+        }
+    }
+
+    /// marks job `seq` as done, releasing the output of jobs waiting on it
+    pub fn complete(&self, seq: usize) {
+        let mut lock = self.0.lock().unwrap();
+        let order = &mut *lock;
+
+        if !order.keep {
+            return;
+        }
+
+        order.waiting.entry(seq).or_default().1 = true;
+
+        while let Some((outputs, done)) = order.waiting.remove(&order.next) {
+            for (out, err) in outputs {
+                order.release(out, err);
+            }
+
+            if !done {
+                // the job is still running and writes directly from now on
+                order.waiting.insert(order.next, (Vec::new(), false));
+                break;
+            }
+
+            order.next += 1;
+        }
+
+        order.reclaim();
+    }
+
+    /// writes all output still waiting, regardless of order
+    ///
+    /// used when jobs are abandoned after halting.
+    pub fn flush(&self) {
+        let mut order = self.0.lock().unwrap();
+
+        for (_, (outputs, _)) in std::mem::take(&mut order.waiting) {
+            for (out, err) in outputs {
+                order.release(out, err);
+            }
+        }
+
+        order.reclaim();
+    }
+
+    /// holds back all output until the returned hold is dropped
+    pub fn hold(&self) -> Hold {
+        self.0.lock().unwrap().held = true;
+        Hold(self.clone())
+    }
+
+    /// the bytes held back in memory and the stdout held back for job `seq`
+    #[cfg(test)]
+    pub fn held(&self, seq: usize) -> (usize, Vec<u8>) {
+        let mut order = self.0.lock().unwrap();
+        let order = &mut *order;
+        let mut out = Vec::new();
+
+        for (held, _) in order.waiting.get(&seq).map_or(&[][..], |w| &w.0) {
+            match held {
+                Held::Memory(mem) => out.extend_from_slice(mem),
+                Held::Region { offset, len } => {
+                    let file = order.spill.as_mut().unwrap();
+                    file.seek(SeekFrom::Start(*offset)).unwrap();
+                    file.take(*len).read_to_end(&mut out).unwrap();
+                }
+                Held::Buffer(_) => panic!("output was not spilled"),
+            }
+        }
+
+        (order.memory, out)
+    }
+
+    /// takes the stdout and stderr held back while holding, in the order written
+    #[cfg(test)]
+    pub fn take_deferred(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut order = self.0.lock().unwrap();
+        let memory = |held: &Held| match held {
+            Held::Memory(mem) => mem.clone(),
+            _ => panic!("output was spilled"),
+        };
+
+        std::mem::take(&mut order.deferred)
+            .iter()
+            .map(|(out, err)| (memory(out), memory(err)))
+            .collect()
+    }
+
+    fn line(&self, stream: Stream, line: &[u8]) {
+        let line = Buffer::Memory(line.to_vec());
+        let mut order = self.0.lock().unwrap();
+
+        match stream {
+            Stream::Stdout => order.emit(line, Buffer::default()),
+            Stream::Stderr => order.emit(Buffer::default(), line),
+        }
+    }
+}
+
+impl Drop for Hold {
+    fn drop(&mut self) {
+        let mut order = (self.0).0.lock().unwrap();
+
+        order.held = false;
+
+        for (out, err) in std::mem::take(&mut order.deferred) {
+            order.release(out, err);
+        }
+
+        order.reclaim();
+    }
+}
+
+impl Order {
+    fn emit(&mut self, out: Buffer, err: Buffer) {
+        if self.held {
+            let held = (self.park(out), self.park(err));
+            self.deferred.push(held);
+        } else {
+            out.write_to(Stream::Stdout);
+            err.write_to(Stream::Stderr);
+        }
+    }
+
+    /// writes held back output unless output is still held
+    fn release(&mut self, out: Held, err: Held) {
+        if self.held {
+            self.deferred.push((out, err));
+        } else {
+            self.unpark(out, Stream::Stdout);
+            self.unpark(err, Stream::Stderr);
+        }
+    }
+
+    /// holds back output, moving it to the spill file once too much is in memory
+    fn park(&mut self, buffer: Buffer) -> Held {
+        let mut buffer = match buffer {
+            Buffer::Memory(mem) if self.memory + mem.len() <= SPILL_SIZE => {
+                self.memory += mem.len();
+                return Held::Memory(mem);
+            }
+            buffer => buffer,
+        };
+
+        match self.spill(&mut buffer) {
+            Ok(held) => held,
+            Err(_) => Held::Buffer(buffer),
+        }
+    }
+
+    fn spill(&mut self, buffer: &mut Buffer) -> io::Result<Held> {
+        let file = match &mut self.spill {
+            Some(file) => file,
+            None => self.spill.insert(spill_file()?),
+        };
+
+        file.seek(SeekFrom::Start(self.spilled))?;
+
+        let len = match buffer {
+            Buffer::Memory(mem) => {
+                file.write_all(mem)?;
+                mem.len() as u64
+            }
+            Buffer::Spilled(spilled) => {
+                spilled.seek(SeekFrom::Start(0))?;
+                io::copy(spilled, file)?
+            }
+        };
+
+        let held = Held::Region {
+            offset: self.spilled,
+            len,
+        };
+
+        self.spilled += len;
+
+        Ok(held)
+    }
+
+    fn unpark(&mut self, held: Held, stream: Stream) {
+        match held {
+            Held::Memory(mem) => {
+                self.memory -= mem.len();
+                write_stream(stream, &mem);
+            }
+            Held::Region { offset, len } => {
+                if let Some(file) = &mut self.spill {
+                    if file.seek(SeekFrom::Start(offset)).is_err() {
+                        return;
+                    }
+
+                    // output that can not be written has nowhere else to go
+                    let _ = match stream {
+                        Stream::Stdout => copy(&mut file.take(len), &mut io::stdout().lock()),
+                        Stream::Stderr => copy(&mut file.take(len), &mut io::stderr().lock()),
+                    };
+                }
+            }
+            Held::Buffer(buffer) => buffer.write_to(stream),
+        }
+    }
+
+    /// empties the spill file once nothing is held back in it
+    fn reclaim(&mut self) {
+        if !self.waiting.is_empty() || !self.deferred.is_empty() {
+            return;
+        }
+
+        if let Some(file) = &self.spill {
+            if file.set_len(0).is_ok() {
+                self.spilled = 0;
+            }
+        }
+    }
+}
+
+impl Prefixer {
+    /// copies `chunk` to `out`, prefixing every line started in it
+    fn copy(&mut self, chunk: &[u8], out: &mut Vec<u8>) {
+        if self.prefix.is_empty() {
+            out.extend_from_slice(chunk);
+            return;
+        }
+
+        for line in chunk.split_inclusive(|b| *b == b'\n') {
+            if self.line_start {
+                out.extend_from_slice(self.prefix.as_bytes());
+            }
+
+            out.extend_from_slice(line);
+            self.line_start = line.ends_with(b"\n");
+        }
+    }
+}
+
+impl Buffer {
+    /// reads a stream to its end, spilling to a temporary file when large
+    ///
+    /// lines are prefixed with `prefix` while reading.
+    pub fn collect<R: Read>(mut pipe: R, prefix: Arc<String>) -> io::Result<Buffer> {
+        let mut buffer = Buffer::Memory(Vec::new());
+        let mut chunk = [0; 8192];
+        let mut tagged = Vec::new();
+        let mut prefixer = Prefixer {
+            prefix,
+            line_start: true,
+        };
+
+        loop {
+            let read = match pipe.read(&mut chunk) {
+                Ok(0) => return Ok(buffer),
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            tagged.clear();
+            prefixer.copy(&chunk[..read], &mut tagged);
+
+            match &mut buffer {
+                Buffer::Memory(mem) => {
+                    mem.extend_from_slice(&tagged);
+
+                    if mem.len() > SPILL_SIZE {
+                        let mut file = spill_file()?;
+                        file.write_all(mem)?;
+                        buffer = Buffer::Spilled(file);
+                    }
+                }
+                Buffer::Spilled(file) => file.write_all(&tagged)?,
+            }
+        }
+    }
+
+    fn write_to(self, stream: Stream) {
+        match self {
+            Buffer::Memory(mem) => write_stream(stream, &mem),
+            Buffer::Spilled(mut file) => {
+                if file.seek(SeekFrom::Start(0)).is_err() {
+                    return;
+                }
+
+                // output that can not be written has nowhere else to go
+                let _ = match stream {
+                    Stream::Stdout => copy(&mut file, &mut io::stdout().lock()),
+                    Stream::Stderr => copy(&mut file, &mut io::stderr().lock()),
+                };
+            }
+        }
+    }
+}
+
+impl Default for Buffer {
+    fn default() -> Buffer {
+        Buffer::Memory(Vec::new())
+    }
+}
+
+impl Capture {
+    /// waits for the output to be collected and writes what was buffered
+    pub fn finish(self) {
+        let out = self.stdout.map(join).unwrap_or_default();
+        let err = self.stderr.map(join).unwrap_or_default();
+
+        self.writer.write(self.seq, out, err);
+    }
+}
+
+fn buffer<R: Read + Send + 'static>(pipe: R, prefix: Arc<String>) -> JoinHandle<Buffer> {
+    spawn(move || Buffer::collect(pipe, prefix).unwrap_or_default())
+}
+
+/// writes every complete line as soon as it is read
+fn lines<R: Read + Send + 'static>(
+    pipe: R,
+    stream: Stream,
+    prefix: Arc<String>,
+    writer: Writer,
+) -> JoinHandle<Buffer> {
+    spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        let mut line = Vec::new();
+
+        loop {
+            line.clear();
+            line.extend_from_slice(prefix.as_bytes());
+
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => writer.line(stream, &line),
+            }
+        }
+
+        Buffer::default()
+    })
+}
+
+fn join(handle: JoinHandle<Buffer>) -> Buffer {
+    handle.join().unwrap_or_default()
+}
+
+/// creates a temporary file that is removed once closed
+fn spill_file() -> io::Result<File> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let path = std::env::temp_dir().join(format!(
+        "yargs-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create_new(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let file = options.open(&path)?;
+
+    // the open file stays usable on unix, elsewhere it may be left behind
+    let _ = remove_file(&path);
+
+    Ok(file)
+}
+
+fn copy<R: Read, W: Write>(file: &mut R, out: &mut W) -> io::Result<()> {
+    io::copy(file, out)?;
+    out.flush()
+}
+
+fn write_stream(stream: Stream, buf: &[u8]) {
+    if buf.is_empty() {
+        return;
+    }
+
+    // output that can not be written has nowhere else to go
+    let _ = match stream {
+        Stream::Stdout => {
+            let mut out = io::stdout().lock();
+            out.write_all(buf).and_then(|_| out.flush())
+        }
+        Stream::Stderr => {
+            let mut err = io::stderr().lock();
+            err.write_all(buf).and_then(|_| err.flush())
+        }
+    };
+}
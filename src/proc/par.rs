@@ -4,94 +4,205 @@ use crate::invoker::DetachedPreview;
 
 use std::collections::VecDeque;
 use std::sync::{Arc, Condvar, Mutex};
-use std::thread::{spawn, JoinHandle};
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::Instant;
 
 #[derive(Debug)]
-pub struct QueueState {
-    queue: VecDeque<DetachedPreview>,
+pub struct QueueState<T> {
+    queue: VecDeque<T>,
     done: bool,
 }
 
+/// Work shared between the main thread and workers
+///
+/// Pushing waits while the queue is full, bounding the memory held
+/// by waiting work.
+pub struct Queue<T> {
+    state: Mutex<QueueState<T>>,
+    /// signaled when work was queued or the queue was closed
+    cond: Condvar,
+    /// signaled when work left the queue
+    space: Condvar,
+    bound: usize,
+}
+
+/// A queued command and its retry state
+#[derive(Debug)]
+pub struct Queued {
+    seq: usize,
+    preview: DetachedPreview,
+    attempt: u32,
+    not_before: Option<Instant>,
+}
+
 pub struct ParRun {
-    state: Arc<Mutex<QueueState>>,
-    cond: Arc<Condvar>,
+    queue: Arc<Queue<Queued>>,
     handles: Vec<JoinHandle<()>>,
+    tracker: Tracker,
 }
 
-impl ParRun {
-    pub fn new(cores: u16) -> ParRun {
+impl<T> Queue<T> {
+    pub fn new(bound: usize) -> Queue<T> {
         let state = QueueState {
             queue: VecDeque::new(),
             done: false,
         };
 
-        let state = Arc::new(Mutex::new(state));
-        let cond = Arc::new(Condvar::new());
+        Queue {
+            state: Mutex::new(state),
+            cond: Condvar::new(),
+            space: Condvar::new(),
+            bound,
+        }
+    }
+
+    /// queues work once there is space, unless the tracker halted
+    ///
+    /// work pushed to a closed queue is dropped.
+    pub fn push(&self, item: T, tracker: &Tracker) {
+        let mut state = self.state.lock().unwrap();
+
+        while state.queue.len() >= self.bound && !state.done {
+            if tracker.halted() {
+                return;
+            }
+
+            state = self.space.wait(state).unwrap();
+        }
+
+        if state.done {
+            return;
+        }
+
+        state.queue.push_back(item);
+
+        self.cond.notify_one();
+    }
+
+    /// queues work again regardless of the bound
+    pub fn requeue(&self, item: T) {
+        self.state.lock().unwrap().queue.push_back(item);
+        self.cond.notify_one();
+    }
+
+    /// takes the next work, waiting for it until the queue is closed
+    pub fn pop(&self) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(item) = state.queue.pop_front() {
+                self.space.notify_one();
+                return Some(item);
+            }
+
+            if state.done {
+                return None;
+            }
+
+            state = self.cond.wait(state).unwrap();
+        }
+    }
+
+    /// no more work is queued, workers stop once the queue is empty
+    pub fn close(&self) {
+        self.state.lock().unwrap().done = true;
+        self.cond.notify_all();
+        self.space.notify_all();
+    }
+
+    /// wakes pushes waiting for space so they notice the tracker halted
+    pub fn halt(&self) {
+        let _lock = self.state.lock().unwrap();
+        self.space.notify_all();
+    }
+}
+
+impl ParRun {
+    pub fn new(cores: u16, tracker: Tracker) -> ParRun {
+        let queue = Arc::new(Queue::new(2 * cores as usize));
         let mut handles = Vec::new();
 
-        for _ in 0..cores {
-            let state = state.clone();
-            let cond = cond.clone();
+        for slot in 1..=cores as usize {
+            let queue = queue.clone();
+            let tracker = tracker.clone();
 
             handles.push(spawn(move || loop {
-                let preview = {
-                    let mut lock = state.lock().unwrap();
-
-                    match lock.queue.pop_front() {
-                        Some(preview) => preview,
-
-                        None => {
-                            if lock.done {
-                                break;
-                            } else {
-                                let mut l = cond.wait(lock).unwrap();
-                                match l.queue.pop_front() {
-                                    Some(preview) => preview,
-                                    None => {
-                                        if l.done {
-                                            break;
-                                        } else {
-                                            continue;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+                if tracker.halted() {
+                    queue.halt();
+                    break;
+                }
+
+                let mut queued: Queued = match queue.pop() {
+                    Some(queued) => queued,
+                    None => break,
                 };
 
-                preview.run().unwrap();
+                if let Some(at) = queued.not_before {
+                    sleep(at.saturating_duration_since(Instant::now()));
+                }
+
+                let job = Job {
+                    seq: queued.seq,
+                    slot,
+                    tag: queued.preview.tag(),
+                    input: queued.preview.input().map(Input::Chunk),
+                    display: &queued.preview,
+                };
+
+                let attempt = tracker.execute(&job, queued.preview.command());
+
+                match tracker.retry(&job, queued.attempt, attempt.outcome) {
+                    Some(delay) => {
+                        queued.attempt += 1;
+                        queued.not_before = Some(Instant::now() + delay);
+
+                        queue.requeue(queued);
+                    }
+                    None => tracker.record(&job, &attempt),
+                }
             }));
         }
 
         ParRun {
-            state,
-            cond,
+            queue,
             handles,
+            tracker,
         }
     }
 }
 
 impl Process for ParRun {
-    fn process(&self, preview: &Preview) {
-        let mut state = self.state.lock().unwrap();
+    fn process(&self, seq: usize, preview: &Preview) {
+        if self.tracker.halted() {
+            return;
+        }
 
-        state.queue.push_back(preview.detach());
+        let queued = Queued {
+            seq,
+            preview: preview.detach(),
+            attempt: 0,
+            not_before: None,
+        };
 
-        self.cond.notify_one();
+        self.queue.push(queued, &self.tracker);
     }
 
-    fn finalize(self) {
-        {
-            let mut state = self.state.lock().unwrap();
+    fn skip(&self, seq: usize) {
+        self.tracker.skip(seq);
+    }
 
-            state.done = true;
+    fn halted(&self) -> bool {
+        self.tracker.halted()
+    }
 
-            self.cond.notify_all();
-        }
+    fn finalize(self) -> Summary {
+        self.queue.close();
 
         for handle in self.handles {
             handle.join().unwrap();
         }
+
+        self.tracker.flush();
+        self.tracker.summary()
     }
 }
@@ -0,0 +1,245 @@
+use super::output::Writer;
+use super::status::unblock_forwarded;
+use crate::invoker::Preview;
+
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const HELP: &str = "\
+y - run this command
+n - do not run this command
+a - run this and all remaining commands
+s - skip this and all remaining commands
+q - run no more commands and quit
+e - edit this command in $EDITOR and run it
+? - print this help
+";
+
+/// Asks on the terminal whether to run commands
+pub struct Prompt {
+    tty: File,
+    reader: RefCell<BufReader<File>>,
+    /// output of running commands is held back while prompting
+    writer: Writer,
+    remaining: Cell<Remaining>,
+}
+
+/// What to do with a command
+pub enum Answer<'a> {
+    Run,
+    /// run an edited version of the command
+    RunEdited(Preview<'a>),
+    Skip,
+    /// run no more commands
+    Quit,
+}
+
+/// What was answered for all remaining commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Remaining {
+    Ask,
+    Run,
+    Skip,
+    Quit,
+}
+
+/// What to do next at the prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Run,
+    Skip,
+    Quit,
+    /// edit the command and run it
+    Edit,
+    /// print the help and ask again
+    Help,
+    /// the answer was not understood, ask again
+    Invalid,
+}
+
+impl Prompt {
+    pub fn new(tty: File, writer: Writer) -> io::Result<Prompt> {
+        let reader = RefCell::new(BufReader::new(tty.try_clone()?));
+
+        Ok(Prompt {
+            tty,
+            reader,
+            writer,
+            remaining: Cell::new(Remaining::Ask),
+        })
+    }
+
+    /// asks until a valid answer was given
+    ///
+    /// a closed terminal quits.
+    pub fn ask<'a>(&self, preview: &Preview<'a>) -> Answer<'a> {
+        let mut hold = None;
+
+        loop {
+            let (next, remaining) = step(self.remaining.get(), || {
+                hold.get_or_insert_with(|| self.writer.hold());
+
+                match self.read_answer(preview) {
+                    Ok(line) => line,
+                    Err(e) => {
+                        eprintln!("/dev/tty: {}", e);
+                        None
+                    }
+                }
+            });
+
+            self.remaining.set(remaining);
+
+            match next {
+                Step::Run => return Answer::Run,
+                Step::Skip => return Answer::Skip,
+                Step::Quit => return Answer::Quit,
+                Step::Edit => match self.edit(preview) {
+                    Ok(Some(edited)) => return Answer::RunEdited(edited),
+                    Ok(None) => return Answer::Skip,
+                    Err(e) => eprintln!("edit: {}", e),
+                },
+                Step::Help => self.write(HELP),
+                Step::Invalid => self.write("answer one of y, n, a, s, q, e or ? for help\n"),
+            }
+        }
+    }
+
+    /// whether no more commands should run
+    pub fn quit(&self) -> bool {
+        self.remaining.get() == Remaining::Quit
+    }
+
+    /// reads a line answering the prompt, `None` once the terminal is closed
+    fn read_answer(&self, preview: &Preview) -> io::Result<Option<String>> {
+        let mut tty = &self.tty;
+
+        write!(tty, "exec {}? [y,n,a,s,q,e,?] ", preview)?;
+        tty.flush()?;
+
+        let mut line = String::new();
+
+        match self.reader.borrow_mut().read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line)),
+        }
+    }
+
+    /// lets the user edit the command, `None` if it was emptied
+    ///
+    /// the edited command runs with `sh -c`.
+    fn edit<'a>(&self, preview: &Preview<'a>) -> io::Result<Option<Preview<'a>>> {
+        let path = edit_file(&format!("{}\n", preview))?;
+
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+
+        // the editor may come with arguments
+        let mut command = Command::new("sh");
+
+        command
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(&path)
+            .stdin(self.tty.try_clone()?)
+            .stdout(self.tty.try_clone()?);
+
+        unblock_forwarded(&mut command);
+
+        let status = command.status();
+
+        let edited = fs::read_to_string(&path);
+        let _ = fs::remove_file(&path);
+
+        if !status?.success() {
+            return Err(io::Error::other("editor failed"));
+        }
+
+        let edited = edited?.trim().to_string();
+
+        if edited.is_empty() {
+            return Ok(None);
+        }
+
+        let args = vec![Cow::from("sh"), Cow::from("-c"), Cow::from(edited)];
+
+        let edited = Preview::new(args).with_tag(preview.tag().cloned());
+
+        Ok(Some(match preview.input() {
+            Some(input) => edited.with_input(input),
+            None => edited,
+        }))
+    }
+
+    fn write(&self, s: &str) {
+        let mut tty = &self.tty;
+        let _ = tty.write_all(s.as_bytes());
+    }
+}
+
+/// the next step for a command and what to do with the commands after it
+///
+/// unless an earlier answer decided for all remaining commands, an answer
+/// is read with `read`, which gives `None` once the terminal is closed.
+pub fn step(remaining: Remaining, read: impl FnOnce() -> Option<String>) -> (Step, Remaining) {
+    match remaining {
+        Remaining::Ask => (),
+        Remaining::Run => return (Step::Run, remaining),
+        Remaining::Skip => return (Step::Skip, remaining),
+        Remaining::Quit => return (Step::Quit, remaining),
+    }
+
+    let line = match read() {
+        Some(line) => line,
+        None => return (Step::Quit, Remaining::Quit),
+    };
+
+    match line.trim().to_ascii_lowercase().as_str() {
+        "yes" | "y" | "true" | "t" => (Step::Run, Remaining::Ask),
+        "no" | "n" | "false" | "f" => (Step::Skip, Remaining::Ask),
+        "all" | "a" => (Step::Run, Remaining::Run),
+        "skip" | "s" => (Step::Skip, Remaining::Skip),
+        "quit" | "q" => (Step::Quit, Remaining::Quit),
+        "edit" | "e" => (Step::Edit, Remaining::Ask),
+        "?" | "help" => (Step::Help, Remaining::Ask),
+        _ => (Step::Invalid, Remaining::Ask),
+    }
+}
+
+/// creates a private file to edit a command in
+///
+/// the file is created anew so nothing placed in the temporary directory
+/// by others is followed or read.
+fn edit_file(contents: &str) -> io::Result<PathBuf> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    loop {
+        let path = std::env::temp_dir().join(format!(
+            "yargs-edit-{}-{}.sh",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(contents.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use super::par::Queue;
+use super::*;
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{spawn, JoinHandle};
+
+/// Streams chunks of input to a fixed pool of long-lived commands
+///
+/// the commands are started with the first chunk, every chunk goes
+/// to whichever command is ready to read it next.
+pub struct RoundRobin {
+    cores: u16,
+    queue: Arc<Queue<Arc<[u8]>>>,
+    handles: RefCell<Vec<JoinHandle<()>>>,
+    tracker: Tracker,
+}
+
+impl RoundRobin {
+    pub fn new(cores: u16, tracker: Tracker) -> RoundRobin {
+        RoundRobin {
+            cores,
+            queue: Arc::new(Queue::new(2 * cores as usize)),
+            handles: RefCell::new(Vec::new()),
+            tracker,
+        }
+    }
+
+    fn start(&self, preview: &Preview) {
+        let mut handles = self.handles.borrow_mut();
+        let running = Arc::new(AtomicUsize::new(self.cores as usize));
+
+        for slot in 1..=self.cores as usize {
+            let preview = preview.detach();
+            let queue = self.queue.clone();
+            let tracker = self.tracker.clone();
+            let running = running.clone();
+
+            handles.push(spawn(move || {
+                let job = Job {
+                    seq: slot - 1,
+                    slot,
+                    tag: preview.tag(),
+                    input: Some(Input::Stream(queue.clone())),
+                    display: &preview,
+                };
+
+                // traced once per command rather than per chunk
+                tracker.trace(&job);
+
+                let attempt = tracker.execute(&job, preview.command());
+                tracker.record(&job, &attempt);
+
+                // input is dropped once no command is left to read it
+                if running.fetch_sub(1, Ordering::SeqCst) == 1 {
+                    queue.close();
+                }
+            }));
+        }
+    }
+}
+
+impl Process for RoundRobin {
+    fn process(&self, _seq: usize, preview: &Preview) {
+        if self.tracker.halted() {
+            return;
+        }
+
+        let chunk = match preview.input() {
+            Some(chunk) => chunk,
+            None => return,
+        };
+
+        if self.handles.borrow().is_empty() {
+            self.start(preview);
+        }
+
+        self.queue.push(chunk, &self.tracker);
+    }
+
+    fn skip(&self, _seq: usize) {}
+
+    fn traces(&self) -> bool {
+        true
+    }
+
+    fn halted(&self) -> bool {
+        self.tracker.halted()
+    }
+
+    fn finalize(self) -> Summary {
+        self.queue.close();
+
+        for handle in self.handles.into_inner() {
+            handle.join().unwrap();
+        }
+
+        self.tracker.flush();
+        self.tracker.summary()
+    }
+}
@@ -1,11 +1,41 @@
 use super::*;
 
-pub struct Run;
+pub struct Run {
+    tracker: Tracker,
+}
+
+impl Run {
+    pub fn new(tracker: Tracker) -> Run {
+        Run { tracker }
+    }
+}
 
 impl Process for Run {
-    fn process(&self, preview: &Preview) {
-        preview.run().unwrap();
+    fn process(&self, seq: usize, preview: &Preview) {
+        if self.tracker.halted() {
+            return;
+        }
+
+        let job = Job {
+            seq,
+            slot: 1,
+            tag: preview.tag(),
+            input: preview.input().map(Input::Chunk),
+            display: preview,
+        };
+
+        self.tracker.run_retrying(&job, || preview.command());
     }
 
-    fn finalize(self) {}
+    fn skip(&self, seq: usize) {
+        self.tracker.skip(seq);
+    }
+
+    fn halted(&self) -> bool {
+        self.tracker.halted()
+    }
+
+    fn finalize(self) -> Summary {
+        self.tracker.summary()
+    }
 }
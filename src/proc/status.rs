@@ -0,0 +1,603 @@
+use super::halt::{Halt, When};
+use super::joblog::JobLog;
+use super::output::{Mode, Writer};
+use super::par::Queue;
+use super::timeout::Timeout;
+use crate::invoker::Tag;
+
+use std::fmt;
+use std::io::{self, Write};
+use std::process::{Child, ChildStdin, Command, ExitStatus};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(unix)]
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+
+/// How a single command ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Exited(i32),
+    Signaled(i32),
+    TimedOut,
+    NotExecutable,
+    NotFound,
+}
+
+/// A job as the tracker runs and records it
+pub struct Job<'a> {
+    /// number of the job in input order, from 0
+    pub seq: usize,
+    /// worker running the job, from 1
+    pub slot: usize,
+    pub tag: Option<&'a Tag>,
+    /// written to the stdin of the command
+    pub input: Option<Input>,
+    /// the command as shown in traces and the job log
+    pub display: &'a dyn fmt::Display,
+}
+
+/// What is written to the stdin of a command
+#[derive(Clone)]
+pub enum Input {
+    /// a chunk of records
+    Chunk(Arc<[u8]>),
+    /// chunks taken from a queue shared with other commands until it is closed
+    Stream(Arc<Queue<Arc<[u8]>>>),
+}
+
+/// One run of a job's command
+#[derive(Debug, Clone, Copy)]
+pub struct Attempt {
+    pub outcome: Outcome,
+    pub start: SystemTime,
+    pub runtime: Duration,
+}
+
+/// Results of all commands run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub jobs: u32,
+    pub failed: u32,
+    pub exited_255: u32,
+    pub signaled: u32,
+    pub timed_out: u32,
+    pub not_executable: u32,
+    pub not_found: u32,
+    /// failed attempts that were run again
+    pub retries: u32,
+}
+
+/// Summary shared between runners and their workers
+///
+/// The tracker also applies the halt policy and knows the running
+/// children so they can be terminated when halting now.
+#[derive(Clone, Default)]
+pub struct Tracker {
+    state: Arc<Mutex<State>>,
+    writer: Writer,
+    joblog: Option<Arc<JobLog>>,
+}
+
+/// How commands are run and when to stop running them
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Policy {
+    pub halt: Option<Halt>,
+    pub timeout: Option<Timeout>,
+    /// time between asking a timed out command to terminate and killing it
+    pub kill_after: Duration,
+    /// times a failed command is run again
+    pub retries: u32,
+    pub retry_delay: Duration,
+    /// double the delay with every retry
+    pub retry_backoff: bool,
+    /// print retries to stderr
+    pub trace: bool,
+    pub output: Mode,
+    /// write the output of commands in the order they were started
+    pub keep_order: bool,
+    /// commands read the terminal, so they stay in the process group of yargs
+    pub tty: bool,
+}
+
+/// Signals blocked in yargs to be forwarded, unblocked again for commands
+#[cfg(unix)]
+static FORWARDED: std::sync::OnceLock<libc::sigset_t> = std::sync::OnceLock::new();
+
+#[derive(Default)]
+struct State {
+    summary: Summary,
+    policy: Policy,
+    halted: bool,
+    running: Vec<u32>,
+    runtimes: Vec<Duration>,
+}
+
+impl From<ExitStatus> for Outcome {
+    #[cfg(unix)]
+    fn from(status: ExitStatus) -> Outcome {
+        match (status.code(), status.signal()) {
+            (Some(code), _) => Outcome::Exited(code),
+            (None, Some(signal)) => Outcome::Signaled(signal),
+            (None, None) => Outcome::Exited(-1),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from(status: ExitStatus) -> Outcome {
+        Outcome::Exited(status.code().unwrap_or(-1))
+    }
+}
+
+impl From<&io::Error> for Outcome {
+    fn from(err: &io::Error) -> Outcome {
+        match err.kind() {
+            io::ErrorKind::NotFound => Outcome::NotFound,
+            _ => Outcome::NotExecutable,
+        }
+    }
+}
+
+impl Input {
+    fn write_to(self, mut stdin: ChildStdin) -> io::Result<()> {
+        match self {
+            Input::Chunk(chunk) => stdin.write_all(&chunk),
+            Input::Stream(queue) => {
+                while let Some(chunk) = queue.pop() {
+                    stdin.write_all(&chunk)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Summary {
+    pub fn record(&mut self, outcome: Outcome) {
+        self.jobs += 1;
+
+        match outcome {
+            Outcome::Exited(0) => (),
+            Outcome::Exited(255) => self.exited_255 += 1,
+            Outcome::Exited(_) => self.failed += 1,
+            Outcome::Signaled(_) => self.signaled += 1,
+            Outcome::TimedOut => self.timed_out += 1,
+            Outcome::NotExecutable => self.not_executable += 1,
+            Outcome::NotFound => self.not_found += 1,
+        }
+    }
+
+    /// the number of commands that did not succeed
+    pub fn unsuccessful(&self) -> u32 {
+        self.failed
+            + self.exited_255
+            + self.signaled
+            + self.timed_out
+            + self.not_executable
+            + self.not_found
+    }
+
+    /// Exit code following xargs
+    ///
+    /// the most severe kind of failure decides the code,
+    /// timed out commands count as killed by a signal.
+    pub fn code(&self) -> i32 {
+        if self.not_found > 0 {
+            127
+        } else if self.not_executable > 0 {
+            126
+        } else if self.signaled > 0 || self.timed_out > 0 {
+            125
+        } else if self.exited_255 > 0 {
+            124
+        } else if self.failed > 0 {
+            123
+        } else {
+            0
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "{} jobs, {} failed, {} retries",
+            self.jobs,
+            self.unsuccessful(),
+            self.retries
+        )
+    }
+}
+
+impl Tracker {
+    pub fn new(policy: Policy) -> Tracker {
+        let state = State {
+            policy,
+            ..State::default()
+        };
+
+        Tracker {
+            state: Arc::new(Mutex::new(state)),
+            writer: Writer::new(policy.keep_order),
+            joblog: None,
+        }
+    }
+
+    /// Passes interrupts on to the process groups of running commands
+    ///
+    /// only needed when commands run in process groups of their own, as the
+    /// terminal does not reach them. Must be called before other threads are
+    /// started, as the signals are blocked for every thread but the one
+    /// waiting for them.
+    #[cfg(unix)]
+    pub fn forward_signals(&self) {
+        let state = self.state.clone();
+
+        if !state.lock().unwrap().policy.grouped() {
+            return;
+        }
+
+        unsafe {
+            let mut set: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut set);
+
+            for signal in &[libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+                libc::sigaddset(&mut set, *signal);
+            }
+
+            libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+            let _ = FORWARDED.set(set);
+
+            spawn(move || {
+                let mut received = 0;
+
+                if libc::sigwait(&set, &mut received) != 0 {
+                    return;
+                }
+
+                // held until yargs ends so no job records the interrupt
+                let state = state.lock().unwrap();
+
+                for pid in &state.running {
+                    signal(*pid, received);
+                }
+
+                // end yargs the way the signal would have
+                libc::signal(received, libc::SIG_DFL);
+                libc::pthread_sigmask(libc::SIG_UNBLOCK, &set, std::ptr::null_mut());
+                libc::raise(received);
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn forward_signals(&self) {}
+
+    /// Writes a record of every job to `joblog`
+    pub fn with_joblog(mut self, joblog: JobLog) -> Tracker {
+        self.joblog = Some(Arc::new(joblog));
+        self
+    }
+
+    /// Passes over job `seq` without running it
+    pub fn skip(&self, seq: usize) {
+        self.writer.complete(seq);
+    }
+
+    /// Runs a command to completion and records its outcome
+    #[cfg(test)]
+    pub fn run(&self, command: Command) -> Outcome {
+        let display = format!("{:?}", command);
+        let job = Job {
+            seq: self.summary().jobs as usize,
+            slot: 1,
+            tag: None,
+            input: None,
+            display: &display,
+        };
+
+        let attempt = self.execute(&job, command);
+        self.record(&job, &attempt);
+
+        attempt.outcome
+    }
+
+    /// Runs commands built by `command` until one succeeds or no retries are left
+    pub fn run_retrying<F>(&self, job: &Job, command: F) -> Outcome
+    where
+        F: Fn() -> Command,
+    {
+        let mut retries = 0;
+
+        loop {
+            let attempt = self.execute(job, command());
+
+            match self.retry(job, retries, attempt.outcome) {
+                Some(delay) => {
+                    sleep(delay);
+                    retries += 1;
+                }
+                None => {
+                    self.record(job, &attempt);
+                    return attempt.outcome;
+                }
+            }
+        }
+    }
+
+    /// Decides whether a failed attempt is run again
+    ///
+    /// `retries` counts the retries already made, the delay to wait
+    /// before retrying is returned. Commands that could not be started
+    /// are not retried.
+    pub fn retry(&self, job: &Job, retries: u32, outcome: Outcome) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let policy = state.policy;
+
+        let retry = match outcome {
+            Outcome::Exited(0) | Outcome::NotFound | Outcome::NotExecutable => false,
+            _ => retries < policy.retries && !state.halted,
+        };
+
+        if !retry {
+            return None;
+        }
+
+        state.summary.retries += 1;
+
+        if policy.trace {
+            eprintln!("retry {}/{}: {}", retries + 1, policy.retries, job.display);
+        }
+
+        if policy.retry_backoff {
+            Some(policy.retry_delay * 2u32.saturating_pow(retries))
+        } else {
+            Some(policy.retry_delay)
+        }
+    }
+
+    /// Runs a command of `job` to completion without recording its outcome
+    ///
+    /// output lines are prefixed with the tag of the job when capturing output.
+    pub fn execute(&self, job: &Job, mut command: Command) -> Attempt {
+        let policy = self.state.lock().unwrap().policy;
+        let output = policy.output;
+        let started = SystemTime::now();
+        let start = Instant::now();
+
+        output.configure(&mut command);
+        unblock_forwarded(&mut command);
+
+        // terminating the group reaches whatever the command started
+        #[cfg(unix)]
+        if policy.grouped() {
+            command.process_group(0);
+        }
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("{}: {}", command.get_program().to_string_lossy(), e);
+
+                return Attempt {
+                    outcome: Outcome::from(&e),
+                    start: started,
+                    runtime: start.elapsed(),
+                };
+            }
+        };
+
+        if let (Some(input), Some(stdin)) = (job.input.clone(), child.stdin.take()) {
+            // commands that stop reading early close the pipe
+            spawn(move || input.write_to(stdin));
+        }
+
+        let pid = child.id();
+        let prefix = job.tag.map(|t| t.render(job.seq + 1));
+        let capture = output.capture(job.seq, &mut child, &self.writer, prefix);
+
+        let (limit, kill_after) = {
+            let mut state = self.state.lock().unwrap();
+
+            if state.halted_now() {
+                terminate(pid);
+            }
+
+            state.running.push(pid);
+
+            let limit = state.policy.timeout.and_then(|t| t.limit(&state.runtimes));
+
+            (limit, state.policy.kill_after)
+        };
+
+        let outcome = match wait(&mut child, limit, kill_after) {
+            Ok(Some(status)) => Outcome::from(status),
+            Ok(None) => Outcome::TimedOut,
+            Err(e) => Outcome::from(&e),
+        };
+
+        let runtime = start.elapsed();
+
+        capture.finish();
+
+        let mut state = self.state.lock().unwrap();
+
+        state.running.retain(|p| *p != pid);
+
+        if outcome == Outcome::Exited(0) {
+            state.runtimes.push(runtime);
+        }
+
+        Attempt {
+            outcome,
+            start: started,
+            runtime,
+        }
+    }
+
+    /// Records the final attempt of `job`
+    pub fn record(&self, job: &Job, attempt: &Attempt) {
+        self.writer.complete(job.seq);
+
+        if let Some(joblog) = &self.joblog {
+            if let Err(e) = joblog.write(job, attempt) {
+                eprintln!("joblog: {}", e);
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        state.summary.record(attempt.outcome);
+
+        let halt = state.policy.halt;
+
+        if !state.halted && halt.is_some_and(|h| h.triggered(&state.summary)) {
+            state.halted = true;
+
+            if state.halted_now() {
+                for pid in &state.running {
+                    terminate(*pid);
+                }
+            }
+        }
+    }
+
+    /// prints the command of `job` to stderr when tracing
+    pub fn trace(&self, job: &Job) {
+        if self.state.lock().unwrap().policy.trace {
+            eprintln!("{}", job.display);
+        }
+    }
+
+    /// whether no further commands should be started
+    pub fn halted(&self) -> bool {
+        self.state.lock().unwrap().halted
+    }
+
+    /// the writer command output goes through
+    pub fn writer(&self) -> Writer {
+        self.writer.clone()
+    }
+
+    /// writes output held back for jobs that never completed
+    pub fn flush(&self) {
+        self.writer.flush();
+    }
+
+    pub fn summary(&self) -> Summary {
+        self.state.lock().unwrap().summary
+    }
+}
+
+impl Policy {
+    /// whether commands run in process groups of their own
+    ///
+    /// only when they may have to be terminated, commands in the background
+    /// are stopped when reading from the terminal.
+    fn grouped(&self) -> bool {
+        let halt_now = self.halt.is_some_and(|h| h.when == When::Now);
+
+        !self.tty && (self.timeout.is_some() || halt_now)
+    }
+}
+
+impl State {
+    fn halted_now(&self) -> bool {
+        self.halted && self.policy.halt.is_some_and(|h| h.when == When::Now)
+    }
+}
+
+/// waits for a child, terminating it once it runs longer than `limit`
+///
+/// returns `None` when the child was terminated for running too long.
+fn wait(
+    child: &mut Child,
+    limit: Option<Duration>,
+    kill_after: Duration,
+) -> io::Result<Option<ExitStatus>> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return child.wait().map(Some),
+    };
+
+    let start = Instant::now();
+    let mut terminated = None;
+    let mut pause = Duration::from_millis(1);
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(if terminated.is_some() {
+                None
+            } else {
+                Some(status)
+            });
+        }
+
+        match terminated {
+            None if start.elapsed() >= limit => {
+                terminate(child.id());
+                terminated = Some(Instant::now());
+            }
+            Some(at) if at.elapsed() >= kill_after => {
+                kill(child)?;
+                child.wait()?;
+                return Ok(None);
+            }
+            _ => (),
+        }
+
+        sleep(pause);
+        pause = (pause * 2).min(Duration::from_millis(50));
+    }
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) {
+    signal(pid, libc::SIGTERM);
+}
+
+#[cfg(unix)]
+fn kill(child: &mut Child) -> io::Result<()> {
+    signal(child.id(), libc::SIGKILL);
+    Ok(())
+}
+
+/// signals the process group of a command, or the command alone when it
+/// shares the group of yargs
+#[cfg(unix)]
+fn signal(pid: u32, signal: libc::c_int) {
+    unsafe {
+        if libc::kill(-(pid as libc::pid_t), signal) != 0 {
+            libc::kill(pid as libc::pid_t, signal);
+        }
+    }
+}
+
+/// lets `command` receive the signals yargs forwards
+///
+/// the signal mask is inherited, so commands would ignore them otherwise.
+#[cfg(unix)]
+pub fn unblock_forwarded(command: &mut Command) {
+    if let Some(set) = FORWARDED.get().copied() {
+        unsafe {
+            command.pre_exec(move || {
+                libc::pthread_sigmask(libc::SIG_UNBLOCK, &set, std::ptr::null_mut());
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn unblock_forwarded(_command: &mut Command) {}
+
+#[cfg(not(unix))]
+fn terminate(_pid: u32) {}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) -> io::Result<()> {
+    child.kill()
+}
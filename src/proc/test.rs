@@ -0,0 +1,572 @@
+use super::output::{Buffer, Writer};
+use super::status::Outcome;
+use super::*;
+
+fn job<'a>(tracker: &Tracker, display: &'a dyn std::fmt::Display) -> Job<'a> {
+    Job {
+        seq: tracker.summary().jobs as usize,
+        slot: 1,
+        tag: None,
+        input: None,
+        display,
+    }
+}
+
+fn summary(outcomes: &[Outcome]) -> Summary {
+    let mut summary = Summary::default();
+
+    for outcome in outcomes {
+        summary.record(*outcome);
+    }
+
+    summary
+}
+
+#[test]
+fn exit_codes() {
+    assert_eq!(summary(&[]).code(), 0, "nothing ran");
+
+    assert_eq!(
+        summary(&[Outcome::Exited(0), Outcome::Exited(0)]).code(),
+        0,
+        "all succeeded"
+    );
+
+    assert_eq!(
+        summary(&[Outcome::Exited(0), Outcome::Exited(1)]).code(),
+        123,
+        "a command failed"
+    );
+
+    assert_eq!(
+        summary(&[Outcome::Exited(2), Outcome::Exited(255)]).code(),
+        124,
+        "a command exited 255"
+    );
+
+    assert_eq!(
+        summary(&[Outcome::Exited(255), Outcome::Signaled(9)]).code(),
+        125,
+        "a command was killed"
+    );
+
+    assert_eq!(
+        summary(&[Outcome::Signaled(9), Outcome::NotExecutable]).code(),
+        126,
+        "a command could not run"
+    );
+
+    assert_eq!(
+        summary(&[Outcome::NotExecutable, Outcome::NotFound]).code(),
+        127,
+        "a command was not found"
+    );
+}
+
+#[test]
+fn outcome_of_commands() {
+    use std::process::Command;
+
+    let tracker = Tracker::default();
+
+    assert_eq!(tracker.run(Command::new("true")), Outcome::Exited(0));
+    assert_eq!(tracker.run(Command::new("false")), Outcome::Exited(1));
+
+    assert_eq!(
+        tracker.run(Command::new("yargs-surely-missing-command")),
+        Outcome::NotFound
+    );
+
+    let mut kill = Command::new("sh");
+    kill.args(["-c", "kill -9 $$"]);
+
+    assert_eq!(tracker.run(kill), Outcome::Signaled(9));
+    assert_eq!(tracker.summary().jobs, 4);
+}
+
+#[test]
+fn halt_parse() {
+    use halt::{Threshold, Trigger, When};
+
+    assert_eq!(
+        "now,fail=1".parse(),
+        Ok(Halt {
+            when: When::Now,
+            trigger: Trigger::Fail(Threshold::Count(1))
+        })
+    );
+
+    assert_eq!(
+        "soon,success=30%".parse(),
+        Ok(Halt {
+            when: When::Soon,
+            trigger: Trigger::Success(Threshold::Percent(30))
+        })
+    );
+
+    assert!("later,fail=1".parse::<Halt>().is_err());
+    assert!("now,fail=0".parse::<Halt>().is_err());
+    assert!("now,fail=101%".parse::<Halt>().is_err());
+    assert!("now,crash=1".parse::<Halt>().is_err());
+    assert!("now".parse::<Halt>().is_err());
+}
+
+#[test]
+fn halt_triggers() {
+    let fail_two: Halt = "soon,fail=2".parse().unwrap();
+    let success_one: Halt = "soon,success=1".parse().unwrap();
+    let fail_half: Halt = "soon,fail=50%".parse().unwrap();
+
+    let one_failed = summary(&[Outcome::Exited(0), Outcome::Exited(1)]);
+    let two_failed = summary(&[Outcome::Exited(0), Outcome::Exited(1), Outcome::NotFound]);
+
+    assert!(!fail_two.triggered(&one_failed));
+    assert!(fail_two.triggered(&two_failed));
+
+    assert!(success_one.triggered(&one_failed));
+    assert!(!success_one.triggered(&summary(&[Outcome::Signaled(9)])));
+
+    assert!(fail_half.triggered(&summary(&[
+        Outcome::Exited(0),
+        Outcome::Exited(1),
+        Outcome::Exited(0),
+        Outcome::Exited(1)
+    ])));
+    assert!(
+        !fail_half.triggered(&summary(&[Outcome::Exited(1)])),
+        "too few jobs for a percentage"
+    );
+    assert!(!fail_half.triggered(&one_failed));
+    assert!(!fail_half.triggered(&summary(&[
+        Outcome::Exited(0),
+        Outcome::Exited(0),
+        Outcome::Exited(1)
+    ])));
+    assert!(!fail_half.triggered(&summary(&[])));
+}
+
+#[test]
+fn timeout_parse() {
+    use std::time::Duration;
+
+    assert_eq!(
+        "500ms".parse(),
+        Ok(Timeout::Fixed(Duration::from_millis(500)))
+    );
+    assert_eq!(
+        "1.5".parse(),
+        Ok(Timeout::Fixed(Duration::from_millis(1500)))
+    );
+    assert_eq!("2m".parse(), Ok(Timeout::Fixed(Duration::from_secs(120))));
+    assert_eq!("1h".parse(), Ok(Timeout::Fixed(Duration::from_secs(3600))));
+    assert_eq!("200%".parse(), Ok(Timeout::Median(200)));
+
+    assert!("10d".parse::<Timeout>().is_err());
+    assert!("s".parse::<Timeout>().is_err());
+    assert!("0%".parse::<Timeout>().is_err());
+}
+
+#[test]
+fn timeout_limits() {
+    use std::time::Duration;
+
+    let secs = |s: &[u64]| {
+        s.iter()
+            .map(|s| Duration::from_secs(*s))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        Timeout::Fixed(Duration::from_secs(3)).limit(&[]),
+        Some(Duration::from_secs(3))
+    );
+
+    assert_eq!(Timeout::Median(200).limit(&[]), None, "no runtimes yet");
+
+    assert_eq!(
+        Timeout::Median(200).limit(&secs(&[9, 1, 2])),
+        Some(Duration::from_secs(4)),
+        "twice the median"
+    );
+}
+
+#[test]
+fn timed_out_commands() {
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    let tracker = Tracker::new(Policy {
+        timeout: Some(Timeout::Fixed(Duration::from_millis(50))),
+        kill_after: Duration::from_millis(50),
+        ..Policy::default()
+    });
+
+    let mut sleep = Command::new("sleep");
+    sleep.arg("5");
+
+    let start = Instant::now();
+
+    assert_eq!(tracker.run(sleep), Outcome::TimedOut, "terminated");
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    let mut stubborn = Command::new("sh");
+    stubborn.args(["-c", "trap '' TERM; sleep 5"]);
+
+    let start = Instant::now();
+
+    assert_eq!(tracker.run(stubborn), Outcome::TimedOut, "killed");
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    assert_eq!(tracker.run(Command::new("true")), Outcome::Exited(0));
+
+    assert_eq!(tracker.summary().timed_out, 2);
+    assert_eq!(tracker.summary().code(), 125);
+
+    let grouped = Tracker::new(Policy {
+        timeout: Some(Timeout::Fixed(Duration::from_millis(50))),
+        kill_after: Duration::from_millis(50),
+        output: Mode::Group,
+        ..Policy::default()
+    });
+
+    // the grandchild holds the captured pipes open unless it is terminated too
+    let mut forked = Command::new("sh");
+    forked.args(["-c", "sleep 5; echo done"]);
+
+    let start = Instant::now();
+
+    assert_eq!(grouped.run(forked), Outcome::TimedOut, "process group");
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn forwarded_signals() {
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    let tracker = Tracker::new(Policy {
+        timeout: Some(Timeout::Fixed(Duration::from_millis(50))),
+        kill_after: Duration::from_secs(10),
+        ..Policy::default()
+    });
+
+    // blocks the signals in this thread, commands must not inherit that
+    tracker.forward_signals();
+
+    let mut sleep = Command::new("sleep");
+    sleep.arg("20");
+
+    let start = Instant::now();
+
+    assert_eq!(tracker.run(sleep), Outcome::TimedOut);
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "terminated, not killed"
+    );
+
+    let mut term = Command::new("sh");
+    term.args(["-c", "kill $$"]);
+
+    assert_eq!(tracker.run(term), Outcome::Signaled(15));
+}
+
+#[test]
+fn retried_commands() {
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    let tracker = Tracker::new(Policy {
+        retries: 2,
+        retry_delay: Duration::from_millis(20),
+        retry_backoff: true,
+        ..Policy::default()
+    });
+
+    let start = Instant::now();
+
+    assert_eq!(
+        tracker.run_retrying(&job(&tracker, &"false"), || Command::new("false")),
+        Outcome::Exited(1)
+    );
+
+    assert!(
+        start.elapsed() >= Duration::from_millis(60),
+        "delays of 20ms and 40ms"
+    );
+
+    assert_eq!(
+        tracker.run_retrying(&job(&tracker, &"missing"), || {
+            Command::new("yargs-surely-missing-command")
+        }),
+        Outcome::NotFound,
+        "commands that can not start are not retried"
+    );
+
+    let dir = std::env::temp_dir().join(format!("yargs-retry-{}", std::process::id()));
+    let flaky = format!("test -e {0} || {{ touch {0}; exit 1; }}", dir.display());
+
+    let command = || {
+        let mut command = Command::new("sh");
+        command.args(["-c", &flaky]);
+        command
+    };
+
+    assert_eq!(
+        tracker.run_retrying(&job(&tracker, &"flaky"), command),
+        Outcome::Exited(0),
+        "succeeds on retry"
+    );
+
+    std::fs::remove_file(&dir).unwrap();
+
+    let summary = tracker.summary();
+
+    assert_eq!(summary.jobs, 3);
+    assert_eq!(summary.retries, 3);
+    assert_eq!(summary.failed, 1);
+}
+
+#[test]
+fn buffered_output() {
+    use std::io::{Read, Seek, SeekFrom};
+
+    match Buffer::collect(&b"small"[..], Default::default()).unwrap() {
+        Buffer::Memory(mem) => assert_eq!(mem, b"small"),
+        Buffer::Spilled(_) => panic!("small output is kept in memory"),
+    }
+
+    let large = vec![b'x'; 3 << 20];
+
+    match Buffer::collect(&large[..], Default::default()).unwrap() {
+        Buffer::Memory(_) => panic!("large output is spilled"),
+        Buffer::Spilled(mut file) => {
+            let mut read = Vec::new();
+            file.seek(SeekFrom::Start(0)).unwrap();
+            file.read_to_end(&mut read).unwrap();
+            assert!(read == large);
+        }
+    }
+
+    // job 0 never completes, so the output of later jobs is held back
+    let writer = Writer::new(true);
+
+    for seq in 1..=3 {
+        let out = vec![b'0' + seq as u8; 600 << 10];
+        writer.write(seq, Buffer::Memory(out), Buffer::default());
+        writer.complete(seq);
+    }
+
+    let (memory, held) = writer.held(1);
+    assert!(memory <= 1 << 20, "held back output is bounded");
+    assert!(held == vec![b'1'; 600 << 10]);
+
+    let (_, held) = writer.held(3);
+    assert!(held == vec![b'3'; 600 << 10], "spilled output");
+}
+
+#[test]
+fn held_output() {
+    let mem = |s: &str| Buffer::Memory(s.as_bytes().to_vec());
+    let writer = Writer::new(true);
+    let hold = writer.hold();
+
+    writer.write(1, mem("later"), Buffer::default());
+    writer.complete(1);
+    writer.write(0, mem("first"), mem("warning"));
+    writer.complete(0);
+
+    assert_eq!(
+        writer.take_deferred(),
+        vec![
+            (b"first".to_vec(), b"warning".to_vec()),
+            (b"later".to_vec(), Vec::new()),
+        ],
+        "output released in order is still held back"
+    );
+
+    drop(hold);
+
+    writer.write(2, Buffer::default(), Buffer::default());
+    assert!(writer.take_deferred().is_empty(), "written once released");
+}
+
+#[test]
+fn line_buffered_output() {
+    use std::process::Command;
+
+    let tracker = Tracker::new(Policy {
+        output: Mode::LineBuffer,
+        ..Policy::default()
+    });
+
+    // lines are deferred in the order they are written while held
+    let _hold = tracker.writer().hold();
+
+    let mut command = Command::new("sh");
+    command.args([
+        "-c",
+        "echo out 1; sleep 0.1; echo err 1 >&2; sleep 0.1; printf 'out 2'",
+    ]);
+
+    assert_eq!(tracker.run(command), Outcome::Exited(0));
+
+    let lines: Vec<(Vec<u8>, Vec<u8>)> = tracker
+        .writer()
+        .take_deferred()
+        .into_iter()
+        .filter(|(out, err)| !out.is_empty() || !err.is_empty())
+        .collect();
+
+    assert_eq!(
+        lines,
+        vec![
+            (b"out 1\n".to_vec(), Vec::new()),
+            (Vec::new(), b"err 1\n".to_vec()),
+            (b"out 2".to_vec(), Vec::new()),
+        ],
+        "whole lines of both streams interleaved, the last without a newline"
+    );
+}
+
+#[test]
+fn joblog_records() {
+    use std::process::Command;
+
+    let path = std::env::temp_dir().join(format!("yargs-joblog-{}", std::process::id()));
+    let tracker = Tracker::default().with_joblog(JobLog::create(&path).unwrap());
+
+    tracker.run(Command::new("true"));
+    tracker.run_retrying(&job(&tracker, &"exit 3"), || {
+        let mut command = Command::new("sh");
+        command.args(["-c", "exit 3"]);
+        command
+    });
+    tracker.run(Command::new("yargs-surely-missing-command"));
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let records: Vec<Vec<&str>> = log.lines().map(|l| l.split('\t').collect()).collect();
+
+    assert_eq!(
+        records[0],
+        vec![
+            "Seq",
+            "Slot",
+            "Starttime",
+            "JobRuntime",
+            "Exitval",
+            "Signal",
+            "Command"
+        ]
+    );
+    assert_eq!(records.len(), 4, "one record per job");
+
+    assert_eq!(records[1][0], "1");
+    assert_eq!(records[1][4], "0");
+    assert_eq!(&records[2][4..], ["3", "0", "exit 3"]);
+    assert_eq!(records[3][0], "3");
+    assert_eq!(records[3][4], "127");
+    assert!(records[1][2].parse::<f64>().unwrap() > 0.0, "start time");
+}
+
+#[test]
+fn resume_from_joblog() {
+    let path = std::env::temp_dir().join(format!("yargs-resume-{}", std::process::id()));
+
+    std::fs::write(
+        &path,
+        "Seq\tSlot\tStarttime\tJobRuntime\tExitval\tSignal\tCommand\n\
+         1\t1\t0.000\t0.001\t0\t0\techo a\n\
+         2\t1\t0.000\t0.001\t1\t0\techo b\n\
+         3\t1\t0.000\t0.001\t-1\t9\techo c\n\
+         3\t1\t0.000\t0.001\t0\t0\techo c\n",
+    )
+    .unwrap();
+
+    let resume = Resume::read(&path, false).unwrap();
+
+    assert_eq!(resume.done(0, "echo a"), Ok(true));
+    assert_eq!(resume.done(1, "echo b"), Ok(true), "failed jobs are done");
+    assert_eq!(resume.done(3, "echo d"), Ok(false), "not recorded");
+    assert!(resume.done(0, "echo changed").is_err(), "changed input");
+
+    let resume = Resume::read(&path, true).unwrap();
+
+    assert_eq!(resume.done(0, "echo a"), Ok(true));
+    assert_eq!(resume.done(1, "echo b"), Ok(false), "failed jobs run again");
+    assert_eq!(resume.done(2, "echo c"), Ok(true), "the last record counts");
+
+    std::fs::remove_file(&path).unwrap();
+
+    let resume = Resume::read(&path, false).unwrap();
+
+    assert_eq!(resume.done(0, "echo a"), Ok(false), "missing log");
+}
+
+#[test]
+fn prompt_steps() {
+    use prompt::{step, Remaining, Step};
+
+    let answer = |line: &str| step(Remaining::Ask, || Some(line.to_string()));
+
+    assert_eq!(answer("y\n"), (Step::Run, Remaining::Ask));
+    assert_eq!(answer("No\n"), (Step::Skip, Remaining::Ask));
+    assert_eq!(answer("a\n"), (Step::Run, Remaining::Run));
+    assert_eq!(answer("s\n"), (Step::Skip, Remaining::Skip));
+    assert_eq!(answer("q\n"), (Step::Quit, Remaining::Quit));
+    assert_eq!(answer("e\n"), (Step::Edit, Remaining::Ask));
+    assert_eq!(answer("?\n"), (Step::Help, Remaining::Ask));
+    assert_eq!(answer("maybe\n"), (Step::Invalid, Remaining::Ask));
+
+    assert_eq!(
+        step(Remaining::Ask, || None),
+        (Step::Quit, Remaining::Quit),
+        "a closed terminal quits"
+    );
+
+    // nothing is read once all remaining commands are decided
+    let unread = || -> Option<String> { panic!("asked again") };
+
+    assert_eq!(step(Remaining::Run, unread), (Step::Run, Remaining::Run));
+    assert_eq!(step(Remaining::Skip, unread), (Step::Skip, Remaining::Skip));
+    assert_eq!(step(Remaining::Quit, unread), (Step::Quit, Remaining::Quit));
+}
+
+#[test]
+fn dry_run() {
+    use crate::invoker::Invoker;
+
+    let invoker = Invoker::new("%", vec!["touch".to_string(), "%".to_string()]);
+    let path = std::env::temp_dir().join(format!("yargs-dry-run-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let log: Log<URun> = Log::DryRun;
+
+    log.process(0, &invoker.preview(&[path]));
+
+    assert!(!std::path::Path::new(path).exists(), "nothing runs");
+    assert_eq!(log.finalize().jobs, 0);
+}
+
+#[test]
+fn round_robin_streams() {
+    use crate::invoker::Invoker;
+
+    let path = std::env::temp_dir().join(format!("yargs-robin-{}", std::process::id()));
+    let script = format!("cat >> {}", path.display());
+
+    let invoker = Invoker::new("%", vec!["sh".into(), "-c".into(), script]);
+    let run = RoundRobin::new(3, Tracker::default());
+
+    for seq in 0..100 {
+        run.process(seq, &invoker.preview(&[]).with_input(vec![b'x'; 1000]));
+    }
+
+    let summary = run.finalize();
+
+    assert_eq!(summary.jobs, 3, "one command per worker");
+    assert_eq!(std::fs::read(&path).unwrap().len(), 100 * 1000, "all input");
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,46 @@
+use crate::duration;
+
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Limit on how long a single command may run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timeout {
+    Fixed(Duration),
+    /// percentage of the median runtime of the commands completed so far
+    Median(u32),
+}
+
+impl Timeout {
+    /// the limit for the next command given the runtimes of successful ones
+    ///
+    /// median based limits only apply once a command completed.
+    pub fn limit(&self, runtimes: &[Duration]) -> Option<Duration> {
+        match self {
+            Timeout::Fixed(limit) => Some(*limit),
+            Timeout::Median(_) if runtimes.is_empty() => None,
+            Timeout::Median(p) => {
+                let mut sorted = runtimes.to_vec();
+                sorted.sort();
+
+                let median = sorted[sorted.len() / 2];
+
+                Some(median * *p / 100)
+            }
+        }
+    }
+}
+
+impl FromStr for Timeout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Timeout, String> {
+        match s.strip_suffix('%') {
+            Some(p) => match p.parse() {
+                Ok(p) if p > 0 => Ok(Timeout::Median(p)),
+                _ => Err(format!("invalid timeout percentage '{}'", s)),
+            },
+            None => duration::parse(s).map(Timeout::Fixed),
+        }
+    }
+}
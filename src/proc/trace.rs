@@ -1,74 +1,70 @@
+use super::prompt::{Answer, Prompt};
 use super::*;
 
-use memchr::memchr;
-
-use std::fs::File;
-use std::io::{Read, Write};
-use std::str::from_utf8;
-
 pub enum Log<Next: Process> {
     Trace(Next),
-    Ask(File, Next),
+    Ask(Prompt, Next),
     Nop(Next),
+    /// print commands to stdout instead of running them
+    DryRun,
 }
 
 impl<Next> Process for Log<Next>
 where
     Next: Process,
 {
-    fn process(&self, preview: &Preview) {
+    fn process(&self, seq: usize, preview: &Preview) {
         match self {
             Log::Trace(n) => {
-                eprintln!("{}", preview);
-                n.process(preview);
+                if !n.traces() {
+                    eprintln!("{}", preview);
+                }
+
+                n.process(seq, preview);
             }
 
             Log::Nop(n) => {
-                n.process(preview);
+                n.process(seq, preview);
             }
 
-            Log::Ask(tty, n) => loop {
-                if let Some(exec) = ask(&tty, preview) {
-                    if exec {
-                        n.process(preview);
-                    }
+            Log::DryRun => println!("{}", preview),
 
-                    break;
-                }
+            Log::Ask(prompt, n) => match prompt.ask(preview) {
+                Answer::Run => n.process(seq, preview),
+                Answer::RunEdited(edited) => n.process(seq, &edited),
+                Answer::Skip | Answer::Quit => n.skip(seq),
             },
         }
     }
 
-    fn finalize(self) {
+    fn skip(&self, seq: usize) {
         match self {
-            Log::Trace(n) => n.finalize(),
-            Log::Nop(n) => n.finalize(),
-            Log::Ask(_, n) => n.finalize(),
+            Log::Trace(n) => n.skip(seq),
+            Log::Nop(n) => n.skip(seq),
+            Log::Ask(_, n) => n.skip(seq),
+            Log::DryRun => (),
         }
     }
-}
-
-fn ask(mut tty: &File, preview: &Preview) -> Option<bool> {
-    write!(tty, "exec '{}'? ", preview).unwrap();
-    tty.flush().unwrap();
-
-    let mut buf = [0u8; 16];
-
-    tty.read(&mut buf).unwrap();
 
-    let pos = memchr(0, &buf)?;
-
-    let pos = if buf[pos.saturating_sub(1)] == 0xA {
-        pos - 1
-    } else {
-        pos
-    };
-
-    let s = from_utf8(&buf[..pos]).ok()?;
+    fn halted(&self) -> bool {
+        match self {
+            Log::Trace(n) => n.halted(),
+            Log::Nop(n) => n.halted(),
+            Log::Ask(prompt, n) => prompt.quit() || n.halted(),
+            Log::DryRun => false,
+        }
+    }
 
-    match s.to_ascii_lowercase().as_str() {
-        "yes" | "y" | "true" | "t" => Some(true),
-        "no" | "n" | "false" | "f" => Some(false),
-        _ => None,
+    fn finalize(self) -> Summary {
+        match self {
+            Log::Trace(n) => {
+                let summary = n.finalize();
+                eprintln!("{}", summary);
+                summary
+            }
+            Log::Nop(n) => n.finalize(),
+            Log::Ask(_, n) => n.finalize(),
+            Log::DryRun => Summary::default(),
+        }
     }
 }
@@ -0,0 +1,23 @@
+/// Parses a byte size like `512`, `64k`, `1M` or `2G` (powers of 1024)
+pub fn parse(s: &str) -> Result<usize, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+
+    let (amount, unit) = s.split_at(split);
+
+    let amount: usize = amount
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", s))?;
+
+    let scale: usize = match unit {
+        "" => 1,
+        "k" | "K" => 1 << 10,
+        "m" | "M" => 1 << 20,
+        "g" | "G" => 1 << 30,
+        _ => return Err(format!("invalid size unit '{}' (use k, M or G)", unit)),
+    };
+
+    match amount.checked_mul(scale) {
+        Some(0) | None => Err(format!("invalid size '{}'", s)),
+        Some(size) => Ok(size),
+    }
+}
@@ -0,0 +1,98 @@
+use super::records::Records;
+
+use regex::bytes::Regex;
+use std::io::{self, BufRead};
+
+/// When a chunk of records is complete
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cut {
+    /// after a number of records
+    Records(u32),
+    /// once it holds at least a number of bytes
+    Bytes(usize),
+}
+
+/// Cuts input into chunks of whole records, delimiters included
+///
+/// With a record start pattern a record spans from a line the pattern
+/// matches at its start up to the next such line.
+pub struct Chunks<R> {
+    lines: Records<R>,
+    cut: Cut,
+    recstart: Option<Regex>,
+    /// line read ahead that starts the next record
+    pending: Option<Vec<u8>>,
+}
+
+impl<R: BufRead> Chunks<R> {
+    pub fn new(lines: Records<R>, cut: Cut, recstart: Option<Regex>) -> Chunks<R> {
+        Chunks {
+            lines,
+            cut,
+            recstart,
+            pending: None,
+        }
+    }
+
+    fn next_record(&mut self) -> Option<io::Result<Vec<u8>>> {
+        let mut record = match self.pending.take() {
+            Some(line) => line,
+            None => match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            },
+        };
+
+        let recstart = match &self.recstart {
+            Some(recstart) => recstart,
+            None => return Some(Ok(record)),
+        };
+
+        loop {
+            match self.lines.next() {
+                None => return Some(Ok(record)),
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(line)) => {
+                    if recstart.find(&line).is_some_and(|m| m.start() == 0) {
+                        self.pending = Some(line);
+                        return Some(Ok(record));
+                    }
+
+                    record.extend_from_slice(&line);
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Chunks<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        let mut chunk = match self.next_record()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+        let mut records = 1;
+
+        loop {
+            let full = match self.cut {
+                Cut::Records(n) => records >= n,
+                Cut::Bytes(n) => chunk.len() >= n,
+            };
+
+            if full {
+                return Some(Ok(chunk));
+            }
+
+            match self.next_record() {
+                None => return Some(Ok(chunk)),
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(record)) => {
+                    chunk.extend_from_slice(&record);
+                    records += 1;
+                }
+            }
+        }
+    }
+}
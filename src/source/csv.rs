@@ -0,0 +1,133 @@
+use std::fmt;
+use std::io::{self, BufRead};
+
+/// Iterator over the rows of CSV formatted input
+///
+/// Fields may be quoted with `"` to contain separators, newlines and
+/// doubled quotes. Empty lines are skipped.
+pub struct Csv<R> {
+    reader: R,
+    separator: u8,
+    done: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CsvError {
+    UnterminatedQuote,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Start,
+    Unquoted,
+    Quoted,
+    QuoteInQuoted,
+}
+
+impl<R: BufRead> Csv<R> {
+    pub fn new(reader: R, separator: u8) -> Csv<R> {
+        Csv {
+            reader,
+            separator,
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Csv<R> {
+    type Item = io::Result<Vec<Vec<u8>>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<Vec<u8>>>> {
+        if self.done {
+            return None;
+        }
+
+        let mut row = Vec::new();
+        let mut field = Vec::new();
+        let mut state = State::Start;
+
+        loop {
+            let buf = match self.reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if buf.is_empty() {
+                self.done = true;
+
+                if state == State::Quoted {
+                    let err =
+                        io::Error::new(io::ErrorKind::InvalidData, CsvError::UnterminatedQuote);
+                    return Some(Err(err));
+                }
+
+                if row.is_empty() && field.is_empty() && state == State::Start {
+                    return None;
+                }
+
+                row.push(field);
+                return Some(Ok(row));
+            }
+
+            let mut used = 0;
+            let mut complete = false;
+
+            for &b in buf {
+                used += 1;
+
+                state = match (state, b) {
+                    (State::Quoted, b'"') => State::QuoteInQuoted,
+                    (State::Quoted, b) => {
+                        field.push(b);
+                        State::Quoted
+                    }
+                    (State::QuoteInQuoted, b'"') => {
+                        field.push(b'"');
+                        State::Quoted
+                    }
+                    (State::Start, b'"') => State::Quoted,
+                    (_, b'\n') => {
+                        if field.last() == Some(&b'\r') && state == State::Unquoted {
+                            field.pop();
+                        }
+
+                        if row.is_empty() && field.is_empty() && state != State::QuoteInQuoted {
+                            state = State::Start;
+                            continue;
+                        }
+
+                        complete = true;
+                        break;
+                    }
+                    (_, b) if b == self.separator => {
+                        row.push(std::mem::take(&mut field));
+                        State::Start
+                    }
+                    (State::QuoteInQuoted, b'\r') => State::QuoteInQuoted,
+                    (_, b) => {
+                        field.push(b);
+                        State::Unquoted
+                    }
+                };
+            }
+
+            self.reader.consume(used);
+
+            if complete {
+                row.push(field);
+                return Some(Ok(row));
+            }
+        }
+    }
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::UnterminatedQuote => write!(fmt, "unterminated quoted csv field"),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
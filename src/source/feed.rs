@@ -0,0 +1,58 @@
+use super::*;
+
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError};
+use std::thread::spawn;
+use std::time::Instant;
+
+/// items read ahead by the reading thread
+const READ_AHEAD: usize = 1024;
+
+type Item = io::Result<Vec<Vec<u8>>>;
+
+/// Input items, either read in place or by a thread so waiting can time out
+pub enum Feed<'a> {
+    Inputs(Inputs<'a>),
+    Thread(Receiver<Item>),
+}
+
+/// What waiting for the next input item gave
+pub enum Next {
+    Item(Item),
+    /// the deadline passed before an item arrived
+    Timeout,
+    End,
+}
+
+impl<'a> Feed<'a> {
+    /// Reads the items of `source` on a thread of its own
+    pub fn spawn(mut source: Source, format: Format) -> Feed<'a> {
+        let (send, receive) = sync_channel(READ_AHEAD);
+
+        spawn(move || {
+            for item in source.buffer().inputs(format) {
+                if send.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Feed::Thread(receive)
+    }
+
+    /// Waits for the next item, giving up at `deadline`
+    ///
+    /// items read in place can not time out.
+    pub fn next(&mut self, deadline: Option<Instant>) -> Next {
+        match (self, deadline) {
+            (Feed::Inputs(inputs), _) => inputs.next().map_or(Next::End, Next::Item),
+            (Feed::Thread(receive), None) => receive.recv().map_or(Next::End, Next::Item),
+            (Feed::Thread(receive), Some(deadline)) => {
+                match receive.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(item) => Next::Item(item),
+                    Err(RecvTimeoutError::Timeout) => Next::Timeout,
+                    Err(RecvTimeoutError::Disconnected) => Next::End,
+                }
+            }
+        }
+    }
+}
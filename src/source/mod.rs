@@ -0,0 +1,188 @@
+use regex::bytes::Regex;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Stdin, StdinLock};
+use std::str::from_utf8;
+
+mod chunks;
+mod csv;
+mod feed;
+mod records;
+mod words;
+
+#[cfg(test)]
+mod test;
+
+pub use chunks::{Chunks, Cut};
+pub use csv::Csv;
+pub use feed::{Feed, Next};
+pub use records::{Delimiter, Records};
+pub use words::Words;
+
+impl From<File> for Source {
+    fn from(f: File) -> Source {
+        Source::File(f)
+    }
+}
+
+impl Default for Source {
+    fn default() -> Source {
+        Source::Stdin(io::stdin())
+    }
+}
+
+pub enum Source {
+    File(File),
+    Stdin(Stdin),
+}
+
+impl Source {
+    pub fn buffer(&mut self) -> SourceBuffer<'_> {
+        match self {
+            Source::File(f) => SourceBuffer::File(BufReader::new(f)),
+            Source::Stdin(s) => SourceBuffer::Stdin(s.lock()),
+        }
+    }
+}
+
+pub enum SourceBuffer<'a> {
+    File(BufReader<&'a mut File>),
+    Stdin(StdinLock<'a>),
+}
+
+/// Input items read from a [SourceBuffer]
+///
+/// Each item is a record split into its columns.
+pub enum Inputs<'a> {
+    Records(Box<Records<SourceBuffer<'a>>>, Option<Delimiter>),
+    Words(Words<SourceBuffer<'a>>),
+    Csv(Csv<SourceBuffer<'a>>),
+}
+
+/// How input is split into items
+#[derive(Debug, Clone)]
+pub enum Format {
+    /// records separated by a delimiter, optionally split into columns
+    Records(Delimiter, Option<Delimiter>),
+    /// xargs style words
+    Words,
+    /// csv rows separated by the byte
+    Csv(u8),
+}
+
+/// A record decoded into text
+#[derive(Debug, PartialEq)]
+pub struct Decoded {
+    pub columns: Vec<String>,
+    /// the parsed object of every column of a json record
+    pub docs: Vec<Value>,
+}
+
+/// Decodes the columns of a record into text
+///
+/// With `json` set the record must hold a single json object.
+pub fn decode(record: Vec<Vec<u8>>, json: bool) -> Result<Decoded, String> {
+    let mut columns = Vec::with_capacity(record.len());
+
+    for column in record {
+        match String::from_utf8(column) {
+            Ok(column) => columns.push(column),
+            Err(e) => {
+                let valid = e.utf8_error().valid_up_to();
+                let bytes = e.into_bytes();
+                let prefix = from_utf8(&bytes[..valid]).unwrap_or_default();
+
+                return Err(format!("invalid utf-8 after '{}'", prefix));
+            }
+        }
+    }
+
+    let mut docs = Vec::new();
+
+    if json {
+        for column in &columns {
+            match serde_json::from_str::<Value>(column) {
+                Ok(doc @ Value::Object(_)) => docs.push(doc),
+                Ok(_) => return Err("record is not a json object".to_string()),
+                Err(e) => return Err(format!("invalid json: {}", e)),
+            }
+        }
+    }
+
+    Ok(Decoded { columns, docs })
+}
+
+impl<'a> SourceBuffer<'a> {
+    /// Splits the buffered input into items of `format`
+    pub fn inputs(self, format: Format) -> Inputs<'a> {
+        match format {
+            Format::Records(delimiter, colsep) => self.records(&delimiter, colsep),
+            Format::Words => self.words(),
+            Format::Csv(separator) => self.csv(separator),
+        }
+    }
+
+    /// Splits the buffered input into records, optionally split into columns
+    pub fn records(self, delimiter: &Delimiter, colsep: Option<Delimiter>) -> Inputs<'a> {
+        Inputs::Records(Box::new(Records::new(self, delimiter)), colsep)
+    }
+
+    /// Cuts the buffered input into chunks of whole records
+    pub fn chunks(self, delimiter: &Delimiter, cut: Cut, recstart: Option<Regex>) -> Chunks<Self> {
+        Chunks::new(
+            Records::new(self, delimiter).with_delimiters(),
+            cut,
+            recstart,
+        )
+    }
+
+    /// Splits the buffered input into xargs style words
+    pub fn words(self) -> Inputs<'a> {
+        Inputs::Words(Words::new(self))
+    }
+
+    /// Splits the buffered input into csv rows
+    pub fn csv(self, separator: u8) -> Inputs<'a> {
+        Inputs::Csv(Csv::new(self, separator))
+    }
+}
+
+impl BufRead for SourceBuffer<'_> {
+    fn consume(&mut self, amt: usize) {
+        match self {
+            SourceBuffer::File(f) => f.consume(amt),
+            SourceBuffer::Stdin(s) => s.consume(amt),
+        }
+    }
+
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            SourceBuffer::File(f) => f.fill_buf(),
+            SourceBuffer::Stdin(s) => s.fill_buf(),
+        }
+    }
+}
+
+impl Read for SourceBuffer<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            SourceBuffer::File(f) => f.read(buf),
+            SourceBuffer::Stdin(s) => s.read(buf),
+        }
+    }
+}
+
+impl Iterator for Inputs<'_> {
+    type Item = io::Result<Vec<Vec<u8>>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<Vec<u8>>>> {
+        match self {
+            Inputs::Records(r, None) => r.next().map(|r| r.map(|record| vec![record])),
+            Inputs::Records(r, Some(colsep)) => {
+                r.next().map(|r| r.map(|record| colsep.split(&record)))
+            }
+            Inputs::Words(w) => w.next().map(|w| w.map(|word| vec![word])),
+            Inputs::Csv(c) => c.next(),
+        }
+    }
+}
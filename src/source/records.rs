@@ -0,0 +1,188 @@
+use memchr::memmem::{self, Finder};
+
+use std::fmt;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+/// Byte sequence separating input records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delimiter(Vec<u8>);
+
+/// Iterator over the records of a buffered reader split by a [Delimiter]
+pub struct Records<R> {
+    reader: R,
+    finder: Finder<'static>,
+    buf: Vec<u8>,
+    scanned: usize,
+    done: bool,
+    keep_delimiters: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DelimiterError {
+    Empty,
+    Escape(String),
+}
+
+impl Delimiter {
+    pub fn newline() -> Delimiter {
+        Delimiter(vec![b'\n'])
+    }
+
+    pub fn null() -> Delimiter {
+        Delimiter(vec![0])
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Splits a record into the parts between occurrences of the delimiter
+    pub fn split(&self, record: &[u8]) -> Vec<Vec<u8>> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+
+        for idx in memmem::find_iter(record, &self.0) {
+            parts.push(record[start..idx].to_vec());
+            start = idx + self.0.len();
+        }
+
+        parts.push(record[start..].to_vec());
+
+        parts
+    }
+}
+
+impl FromStr for Delimiter {
+    type Err = DelimiterError;
+
+    /// Parses a delimiter, expanding `\n`, `\r`, `\t`, `\0`, `\\` and `\xHH` escapes
+    fn from_str(s: &str) -> Result<Delimiter, DelimiterError> {
+        let mut bytes = Vec::with_capacity(s.len());
+        let mut rest = s.as_bytes();
+
+        while let Some((&b, tail)) = rest.split_first() {
+            rest = tail;
+
+            if b != b'\\' {
+                bytes.push(b);
+                continue;
+            }
+
+            let (&escape, tail) = rest
+                .split_first()
+                .ok_or_else(|| DelimiterError::Escape("\\".to_string()))?;
+            rest = tail;
+
+            match escape {
+                b'n' => bytes.push(b'\n'),
+                b'r' => bytes.push(b'\r'),
+                b't' => bytes.push(b'\t'),
+                b'0' => bytes.push(0),
+                b'\\' => bytes.push(b'\\'),
+                b'x' => {
+                    let hex = rest
+                        .get(..2)
+                        .and_then(|h| std::str::from_utf8(h).ok())
+                        .and_then(|h| u8::from_str_radix(h, 16).ok())
+                        .ok_or_else(|| {
+                            let len = rest.len().min(2);
+                            DelimiterError::Escape(format!(
+                                "\\x{}",
+                                String::from_utf8_lossy(&rest[..len])
+                            ))
+                        })?;
+
+                    bytes.push(hex);
+                    rest = &rest[2..];
+                }
+                other => {
+                    return Err(DelimiterError::Escape(format!("\\{}", other as char)));
+                }
+            }
+        }
+
+        if bytes.is_empty() {
+            return Err(DelimiterError::Empty);
+        }
+
+        Ok(Delimiter(bytes))
+    }
+}
+
+impl fmt::Display for DelimiterError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DelimiterError::Empty => write!(fmt, "delimiter may not be empty"),
+            DelimiterError::Escape(e) => write!(fmt, "invalid escape sequence '{}'", e),
+        }
+    }
+}
+
+impl<R: BufRead> Records<R> {
+    pub fn new(reader: R, delimiter: &Delimiter) -> Records<R> {
+        Records {
+            reader,
+            finder: Finder::new(delimiter.as_bytes()).into_owned(),
+            buf: Vec::new(),
+            scanned: 0,
+            done: false,
+            keep_delimiters: false,
+        }
+    }
+
+    /// Keeps the delimiter at the end of each record
+    pub fn with_delimiters(mut self) -> Records<R> {
+        self.keep_delimiters = true;
+        self
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        let dlen = self.finder.needle().len();
+
+        loop {
+            if let Some(pos) = self.finder.find(&self.buf[self.scanned..]) {
+                let end = self.scanned + pos;
+                let mut record: Vec<u8> = self.buf.drain(..end + dlen).collect();
+
+                if !self.keep_delimiters {
+                    record.truncate(end);
+                }
+
+                self.scanned = 0;
+
+                return Some(Ok(record));
+            }
+
+            if self.done {
+                return if self.buf.is_empty() {
+                    None
+                } else {
+                    self.scanned = 0;
+                    Some(Ok(std::mem::take(&mut self.buf)))
+                };
+            }
+
+            // a delimiter may straddle the previous and next read
+            self.scanned = self.buf.len().saturating_sub(dlen - 1);
+
+            let available = match self.reader.fill_buf() {
+                Ok(available) => available,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if available.is_empty() {
+                self.done = true;
+            } else {
+                let len = available.len();
+                self.buf.extend_from_slice(available);
+                self.reader.consume(len);
+            }
+        }
+    }
+}
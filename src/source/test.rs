@@ -0,0 +1,314 @@
+use super::records::DelimiterError;
+use super::*;
+
+fn split(input: &[u8], delimiter: &str) -> Vec<Vec<u8>> {
+    let delimiter: Delimiter = delimiter.parse().unwrap();
+
+    Records::new(input, &delimiter)
+        .map(|record| record.unwrap())
+        .collect()
+}
+
+fn parse(delimiter: &str) -> Result<Vec<u8>, DelimiterError> {
+    delimiter
+        .parse::<Delimiter>()
+        .map(|delimiter| delimiter.as_bytes().to_vec())
+}
+
+#[test]
+fn delimiter_parse() {
+    assert_eq!(parse(","), Ok(vec![b',']), "single byte");
+
+    assert_eq!(parse("\\r\\n"), Ok(vec![b'\r', b'\n']), "escaped crlf");
+
+    assert_eq!(parse("\\t"), Ok(vec![b'\t']), "escaped tab");
+    assert_eq!(parse("\\0"), Ok(vec![0]), "escaped null");
+    assert_eq!(parse("\\x1e"), Ok(vec![0x1e]), "hex escape");
+
+    assert_eq!(parse("--"), Ok(vec![b'-', b'-']), "multi byte string");
+
+    assert_eq!(parse(""), Err(DelimiterError::Empty));
+
+    assert_eq!(parse("\\q"), Err(DelimiterError::Escape("\\q".to_string())));
+
+    assert_eq!(
+        parse("\\x4"),
+        Err(DelimiterError::Escape("\\x4".to_string()))
+    );
+}
+
+#[test]
+fn records() {
+    assert_eq!(
+        split(b"foo\nbar\n", "\\n"),
+        vec![b"foo".to_vec(), b"bar".to_vec()],
+        "trailing delimiter"
+    );
+
+    assert_eq!(
+        split(b"foo,bar", ","),
+        vec![b"foo".to_vec(), b"bar".to_vec()],
+        "no trailing delimiter"
+    );
+
+    assert_eq!(
+        split(b"foo\r\nbar\nbaz\r\n", "\\r\\n"),
+        vec![b"foo".to_vec(), b"bar\nbaz".to_vec()],
+        "multi byte delimiter"
+    );
+
+    assert_eq!(
+        split(b"a,,b", ","),
+        vec![b"a".to_vec(), b"".to_vec(), b"b".to_vec()],
+        "empty record"
+    );
+
+    assert_eq!(split(b"", ","), Vec::<Vec<u8>>::new(), "empty input");
+}
+
+#[test]
+fn records_straddling_reads() {
+    let input: &[u8] = b"one<>two<>three";
+    let reader = std::io::BufReader::with_capacity(3, input);
+    let delimiter: Delimiter = "<>".parse().unwrap();
+
+    let records: Vec<Vec<u8>> = Records::new(reader, &delimiter)
+        .map(|record| record.unwrap())
+        .collect();
+
+    assert_eq!(
+        records,
+        vec![b"one".to_vec(), b"two".to_vec(), b"three".to_vec()]
+    );
+}
+
+fn words(input: &[u8]) -> Result<Vec<String>, String> {
+    Words::new(input)
+        .map(|word| {
+            word.map(|w| String::from_utf8(w).unwrap())
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn words_split() {
+    assert_eq!(
+        words(b"foo bar\tbaz\n\nqux\n"),
+        Ok(vec![
+            "foo".to_string(),
+            "bar".to_string(),
+            "baz".to_string(),
+            "qux".to_string()
+        ]),
+        "blank separated"
+    );
+
+    assert_eq!(
+        words(b"'hello world' \"it's\" a\\ b"),
+        Ok(vec![
+            "hello world".to_string(),
+            "it's".to_string(),
+            "a b".to_string()
+        ]),
+        "quotes and escapes"
+    );
+
+    assert_eq!(
+        words(b"pre'fix 'post \"\" ''"),
+        Ok(vec![
+            "prefix post".to_string(),
+            "".to_string(),
+            "".to_string()
+        ]),
+        "adjacent and empty quotes"
+    );
+
+    assert_eq!(words(b"  \n "), Ok(vec![]), "only blanks");
+}
+
+#[test]
+fn words_errors() {
+    assert_eq!(
+        words(b"foo 'bar"),
+        Err("unmatched single quote".to_string())
+    );
+
+    assert_eq!(
+        words(b"\"foo\nbar\""),
+        Err("unmatched double quote".to_string())
+    );
+
+    assert_eq!(
+        words(b"foo\\"),
+        Err("input ends with a backslash".to_string())
+    );
+}
+
+fn csv(input: &[u8], separator: u8) -> Result<Vec<Vec<String>>, String> {
+    Csv::new(input, separator)
+        .map(|row| {
+            row.map(|fields| {
+                fields
+                    .into_iter()
+                    .map(|f| String::from_utf8(f).unwrap())
+                    .collect()
+            })
+            .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn csv_rows() {
+    assert_eq!(
+        csv(b"a,b,c\n1,2,3\n", b','),
+        Ok(vec![
+            vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            vec!["1".to_string(), "2".to_string(), "3".to_string()]
+        ]),
+        "plain rows"
+    );
+
+    assert_eq!(
+        csv(b"\"a,b\",\"say \"\"hi\"\"\"\r\n\"multi\nline\",\n", b','),
+        Ok(vec![
+            vec!["a,b".to_string(), "say \"hi\"".to_string()],
+            vec!["multi\nline".to_string(), "".to_string()]
+        ]),
+        "quoted fields"
+    );
+
+    assert_eq!(
+        csv(b"a\tb\n\n\r\nc\td", b'\t'),
+        Ok(vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["c".to_string(), "d".to_string()]
+        ]),
+        "other separator and blank lines"
+    );
+
+    assert_eq!(
+        csv(b"a,\"b\n", b','),
+        Err("unterminated quoted csv field".to_string())
+    );
+}
+
+#[test]
+fn columns() {
+    let colsep: Delimiter = "::".parse().unwrap();
+
+    assert_eq!(
+        colsep.split(b"a::b::::c"),
+        vec![b"a".to_vec(), b"b".to_vec(), b"".to_vec(), b"c".to_vec()]
+    );
+
+    assert_eq!(colsep.split(b"abc"), vec![b"abc".to_vec()]);
+}
+
+#[test]
+fn decode_records() {
+    assert_eq!(
+        decode(vec![b"a".to_vec(), b"b".to_vec()], false),
+        Ok(Decoded {
+            columns: vec!["a".to_string(), "b".to_string()],
+            docs: vec![],
+        })
+    );
+
+    assert_eq!(
+        decode(vec![b"ok\xff".to_vec()], false),
+        Err("invalid utf-8 after 'ok'".to_string())
+    );
+
+    assert_eq!(
+        decode(vec![br#"{"a":1}"#.to_vec()], true),
+        Ok(Decoded {
+            columns: vec![r#"{"a":1}"#.to_string()],
+            docs: vec![serde_json::json!({"a": 1})],
+        }),
+        "the parsed object is kept"
+    );
+
+    assert_eq!(
+        decode(vec![b"[1]".to_vec()], true),
+        Err("record is not a json object".to_string())
+    );
+
+    assert!(decode(vec![b"{".to_vec()], true).is_err());
+}
+
+fn chunks(input: &[u8], cut: Cut, recstart: Option<&str>) -> Vec<String> {
+    let lines = Records::new(input, &Delimiter::newline()).with_delimiters();
+    let recstart = recstart.map(|r| r.parse().unwrap());
+
+    Chunks::new(lines, cut, recstart)
+        .map(|chunk| String::from_utf8(chunk.unwrap()).unwrap())
+        .collect()
+}
+
+#[test]
+fn chunks_of_records() {
+    assert_eq!(
+        chunks(b"a\nb\nc\n", Cut::Records(2), None),
+        vec!["a\nb\n", "c\n"],
+        "by record count"
+    );
+
+    assert_eq!(
+        chunks(b"aa\nbb\ncc\ndd", Cut::Bytes(4), None),
+        vec!["aa\nbb\n", "cc\ndd"],
+        "by size rounded up to whole records"
+    );
+
+    assert_eq!(
+        chunks(b"head\n>a\n1\n>b\n2\n3\n>c\n", Cut::Records(1), Some(">")),
+        vec!["head\n", ">a\n1\n", ">b\n2\n3\n", ">c\n"],
+        "records starting at a pattern"
+    );
+
+    assert_eq!(
+        chunks(b"x>a\n>b\n", Cut::Records(1), Some(">")),
+        vec!["x>a\n", ">b\n"],
+        "the pattern must match at the start of a line"
+    );
+
+    assert!(chunks(b"", Cut::Records(1), None).is_empty());
+}
+
+#[test]
+fn feed_times_out() {
+    use std::io::Write;
+    use std::os::unix::io::FromRawFd;
+    use std::time::{Duration, Instant};
+
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+
+    let (read, mut write) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+    let format = Format::Records(Delimiter::newline(), None);
+    let mut feed = Feed::spawn(read.into(), format);
+
+    let soon = || Some(Instant::now() + Duration::from_millis(50));
+
+    write.write_all(b"a\n").unwrap();
+
+    assert!(
+        matches!(feed.next(None), Next::Item(Ok(item)) if item == vec![b"a".to_vec()]),
+        "item read"
+    );
+    assert!(
+        matches!(feed.next(soon()), Next::Timeout),
+        "waiting for more"
+    );
+
+    write.write_all(b"b\n").unwrap();
+    drop(write);
+
+    assert!(
+        matches!(feed.next(soon()), Next::Item(Ok(item)) if item == vec![b"b".to_vec()]),
+        "item before the deadline"
+    );
+    assert!(matches!(feed.next(soon()), Next::End), "end of input");
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+use std::io::{self, BufRead};
+
+/// Iterator over xargs style words of a buffered reader
+///
+/// Words are separated by blanks, quotes group blanks into a single word and
+/// a backslash escapes the following character.
+pub struct Words<R> {
+    reader: R,
+    done: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum WordError {
+    UnterminatedQuote(char),
+    TrailingEscape,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Blank,
+    Word,
+    Escape,
+    Quoted(u8),
+}
+
+impl<R: BufRead> Words<R> {
+    pub fn new(reader: R) -> Words<R> {
+        Words {
+            reader,
+            done: false,
+        }
+    }
+
+    fn fail(&mut self, err: WordError) -> Option<io::Result<Vec<u8>>> {
+        self.done = true;
+
+        Some(Err(io::Error::new(io::ErrorKind::InvalidData, err)))
+    }
+}
+
+impl<R: BufRead> Iterator for Words<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        if self.done {
+            return None;
+        }
+
+        let mut word = Vec::new();
+        let mut state = State::Blank;
+
+        loop {
+            let buf = match self.reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if buf.is_empty() {
+                self.done = true;
+
+                return match state {
+                    State::Blank => None,
+                    State::Word => Some(Ok(word)),
+                    State::Escape => self.fail(WordError::TrailingEscape),
+                    State::Quoted(q) => self.fail(WordError::UnterminatedQuote(q as char)),
+                };
+            }
+
+            let mut used = 0;
+            let mut complete = false;
+            let mut unterminated = None;
+
+            for &b in buf {
+                used += 1;
+
+                state = match (state, b) {
+                    (State::Blank, b' ' | b'\t' | b'\n') => State::Blank,
+                    (State::Word, b' ' | b'\t' | b'\n') => {
+                        complete = true;
+                        break;
+                    }
+                    (State::Blank | State::Word, b'\\') => State::Escape,
+                    (State::Blank | State::Word, b'\'' | b'"') => State::Quoted(b),
+                    (State::Quoted(q), b) if q == b => State::Word,
+                    (State::Quoted(q), b'\n') => {
+                        unterminated = Some(q as char);
+                        break;
+                    }
+                    (State::Quoted(q), b) => {
+                        word.push(b);
+                        State::Quoted(q)
+                    }
+                    (_, b) => {
+                        word.push(b);
+                        State::Word
+                    }
+                };
+            }
+
+            self.reader.consume(used);
+
+            if let Some(q) = unterminated {
+                return self.fail(WordError::UnterminatedQuote(q));
+            }
+
+            if complete {
+                return Some(Ok(word));
+            }
+        }
+    }
+}
+
+impl fmt::Display for WordError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WordError::UnterminatedQuote(q) => write!(fmt, "unmatched {} quote", quote_name(*q)),
+            WordError::TrailingEscape => write!(fmt, "input ends with a backslash"),
+        }
+    }
+}
+
+impl std::error::Error for WordError {}
+
+fn quote_name(q: char) -> &'static str {
+    if q == '"' {
+        "double"
+    } else {
+        "single"
+    }
+}